@@ -8,24 +8,58 @@
 //! # Environment Variables
 //!
 //! - `OPEN_AI_KEY`: The API key for authenticating with the OpenAI API.
-//! - `OPEN_AI_ORG`: The organization ID for the OpenAI API.
+//! - `OPEN_AI_ORG`: The organization ID for the OpenAI API. `CallParams::organization` overrides it per call.
+//! - `OPEN_AI_PROJECT` (optional): Project ID sent in the `OpenAI-Project` header. `CallParams::project` overrides it per call.
+//! - `OPEN_AI_REGION` (optional): Data-residency region, `us` (the default `api.openai.com` endpoint) or `eu` (`eu.api.openai.com`). Any other value is an error.
+//! - `OPEN_AI_MODEL` (optional): Default model when none is passed per call. Falls back to `gpt-4o`.
+//! - `OPEN_AI_USER_AGENT` (optional): Overrides the default `rust-autogpt/<version>` user agent.
+//! - `OPEN_AI_BETA` (optional): Value of the `OpenAI-Beta` header, e.g. `assistants=v2`. The header is omitted when unset.
+//! - `OPEN_AI_MAX_RETRIES` (optional): Times a request failing with a retryable error (rate limiting, server errors, timeouts) is retried with exponential backoff. Defaults to `0`.
+//! - `OPEN_AI_POOL_MAX_IDLE_PER_HOST` / `OPEN_AI_POOL_IDLE_TIMEOUT_SECS` (optional): Connection pool tuning for high-concurrency workloads. Default to no idle-connection limit and a 90 second idle timeout.
+//! - `OPEN_AI_STREAM_IDLE_TIMEOUT_SECS` (optional): Aborts a streamed completion that receives nothing for this many seconds while staying connected. Unset by default, i.e. no limit.
+//! - `OPEN_AI_GZIP` (optional): Set to `0`, `false` or `off` to stop requesting gzip-compressed responses, e.g. behind a proxy that mishandles them.
+//! - `OPEN_AI_DANGER_ACCEPT_INVALID_CERTS` (optional): Set to `1`, `true` or `on` to accept self-signed or otherwise invalid TLS certificates, for local development servers only. Off by default and logged as a warning when enabled.
+//! - `OPEN_AI_RECORD` / `OPEN_AI_REPLAY` (optional): Path of a JSONL recording. `OPEN_AI_RECORD` appends every chat completion response to it; `OPEN_AI_REPLAY` serves responses from it without contacting the API, failing on requests that were never recorded.
+//! - `OPEN_AI_MODEL_TEMPERATURES` (optional): Default temperature per model, e.g. `gpt-4o=0.7,gpt-4o-mini=0.2`. A temperature passed in `CallParams` always wins; models not listed use `0.1`.
+//! - `OPEN_AI_SEED` (optional): Seed sent with every chat completion for best-effort reproducible sampling. `CallParams::seed` overrides it per call.
+//! - `OPEN_AI_SYSTEM_PREAMBLE_FILE` / `OPEN_AI_SYSTEM_PREAMBLE` (optional): System prompt, e.g. safety guardrails, prepended to every conversation, read from a file or given inline. The file wins if both are set; an unreadable file is an error.
+//! - `OPEN_AI_DATETIME_CONTEXT` (optional): Set to `1`, `true` or `on` to add the current UTC date and time as a system message at the start of every conversation. Off by default.
+//! - `OPEN_AI_DATETIME_FORMAT` (optional): `strftime` format of that date and time, `%Y-%m-%d %H:%M UTC` by default. An invalid format is an error.
+//!
+//! With the default `dotenv` cargo feature these are also loaded from a `.env` file. Disable
+//! default features to rely solely on the process environment.
 //!
 //! # Functions
 //!
-//! - `call_gpt`: Asynchronously sends a list of messages to the OpenAI GPT API and returns the generated response as a `Result<String, Box<dyn std::error::Error + Send>>`.
+//! - `call_gpt`: Asynchronously sends a list of messages to the OpenAI GPT API and returns the generated response as a `Result<String, CallGptError>`.
+//! - `call_gpt_with_params`: Like `call_gpt`, with per-call overrides (model, temperature, extra headers) from a `CallParams`.
+//! - `call_gpt_detailed`: Like `call_gpt_with_params`, returning a `CompletionResult` that also records the effective model (e.g. after falling back to `fallback_model`) and temperature.
+//! - `call_gpt_choices`: Returns every choice in the response, with its index, role and finish reason, for callers that need more than the first choice's content.
+//! - `call_gpt_raw`: Returns the first choice's content together with the parsed `APIResponse`, for fields the convenience results omit.
+//! - `call_embeddings` / `call_embeddings_chunked`: Embed texts, splitting inputs too long for the model into overlapping chunks.
+//! - `call_gpt_stream`: Streams the completion as a channel of content deltas.
+//! - `call_gpt_stream_with`: Streams the completion into a callback invoked per content delta, returning the assembled content.
+//! - `call_gpt_stream_detailed`: Like `call_gpt_stream_with`, with per-call overrides, also returning the token usage when `CallParams::include_usage` is set.
+//! - `call_completion`: Sends a fully specified `ChatCompletion` request (model, temperature and messages) and returns the generated response.
+//! - `verify_credentials`: Validates the configured key and organization without generating a completion.
+//! - `list_models`: Lists the IDs of the models available to the configured key.
+//! - `moderate`: Runs text through the moderation endpoint and reports whether it was flagged.
+//! - `call_gpt_with_client` / `call_completion_with_client`: The same calls using an injected `reqwest::Client` and base URL, e.g. to target a mock server in tests. Only the credentials are read from the environment.
+//!
+//! These functions are thin wrappers around [`GptClient`](crate::apis::client::GptClient), which library users can construct once with explicit configuration.
+//! All of them count against the process-wide limit set with
+//! [`set_max_concurrent_requests`](crate::apis::concurrency::set_max_concurrent_requests);
+//! a `GptClient` can instead be given a limit of its own with `GptConfig::concurrency_limit`.
 //!
 //! # Example
 //!
-//! ```rust
-//! use crate::apis::call_request::call_gpt;
-//! use crate::models::general::llm::Message;
+//! ```rust,no_run
+//! use rust_autogpt::apis::call_request::call_gpt;
+//! use rust_autogpt::models::general::llm::Message;
 //!
 //! #[tokio::main]
 //! async fn main() {
-//!     let message = Message {
-//!         role: "user".to_string(),
-//!         content: "Hello, how are you?".to_string(),
-//!     };
+//!     let message = Message::new("user", "Hello, how are you?");
 //!     let messages = vec![message];
 //!
 //!     match call_gpt(messages).await {
@@ -37,18 +71,33 @@
 //!
 //! # Tests
 //!
-//! The module includes a test function `tests_call_to_openai` that verifies the `call_gpt` function by sending a test message and checking the response.
-use crate::models::general::llm::{APIResponse, ChatCompletion, Message};
-use dotenv::dotenv;
-use reqwest::header::{HeaderMap, HeaderValue};
+//! The module includes a test function `tests_call_to_openai` that verifies the `call_gpt_with_client` function against a mock OpenAI server.
+use crate::apis::client::{GptClient, GptConfig};
+use crate::apis::error::CallGptError;
+use crate::apis::params::CallParams;
+use crate::models::general::llm::{
+    APIChoice, APIResponse, ChatCompletion, CompletionResult, EmbeddedChunk, Message,
+    ModerationResult, StreamedCompletion,
+};
 use reqwest::Client;
-use std::env;
+use tokio::sync::{mpsc, OnceCell};
+
+/// Base URL of the OpenAI API.
+pub const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// Model used when neither the caller nor `OPEN_AI_MODEL` specifies one.
+pub const DEFAULT_MODEL: &str = "gpt-4o";
+
+/// Sampling temperature used when neither the caller nor `OPEN_AI_MODEL_TEMPERATURES`
+/// specifies one for the model.
+pub const DEFAULT_TEMPERATURE: f32 = 0.1;
 
 /// Asynchronously sends a list of messages to the OpenAI GPT API and returns the generated response.
 ///
 /// This function constructs an HTTP request to the OpenAI GPT API using the provided messages,
 /// and retrieves the generated response. It handles the API key and organization ID through
-/// environment variables `OPEN_AI_KEY` and `OPEN_AI_ORG`.
+/// environment variables `OPEN_AI_KEY` and `OPEN_AI_ORG`, which are read once to build a
+/// shared default [`GptClient`]. Use a [`GptClient`] directly for explicit configuration.
 ///
 /// # Arguments
 ///
@@ -58,27 +107,26 @@ use std::env;
 ///
 /// A `Result` which is:
 /// * `Ok(String)` containing the generated response from the OpenAI GPT API.
-/// * `Err(Box<dyn std::error::Error + Send>)` if there was an error during the request.
+/// * `Err(CallGptError)` if there was an error during the request.
 ///
 /// # Errors
 ///
 /// This function will return an error if:
 /// * The environment variables `OPEN_AI_KEY` or `OPEN_AI_ORG` are not set.
 /// * There is an issue with constructing the HTTP request.
-/// * The HTTP request to the OpenAI GPT API fails.
+/// * The HTTP request to the OpenAI GPT API fails or returns a non-success status.
+/// * The response contains no choices.
+/// * The response body cannot be parsed. The error then carries a truncated copy of the body.
 ///
 /// # Example
 ///
-/// ```rust
-/// use crate::apis::call_request::call_gpt;
-/// use crate::models::general::llm::Message;
+/// ```rust,no_run
+/// use rust_autogpt::apis::call_request::call_gpt;
+/// use rust_autogpt::models::general::llm::Message;
 ///
 /// #[tokio::main]
 /// async fn main() {
-///     let message = Message {
-///         role: "user".to_string(),
-///         content: "Hello, how are you?".to_string(),
-///     };
+///     let message = Message::new("user", "Hello, how are you?");
 ///     let messages = vec![message];
 ///
 ///     match call_gpt(messages).await {
@@ -87,94 +135,449 @@ use std::env;
 ///     }
 /// }
 /// ```
-pub async fn call_gpt(messages: Vec<Message>) -> Result<String, Box<dyn std::error::Error + Send>> {
-    dotenv().ok();
-
-    // Extract API Key information
-    let api_key: String =
-        env::var("OPEN_AI_KEY").expect("OPEN_AI_KEY not found in environment variables");
-    let api_org: String =
-        env::var("OPEN_AI_ORG").expect("OPEN_AI_ORG not found in environment variables");
-
-    // Confirm endpoint
-    let url: &str = "https://api.openai.com/v1/chat/completions";
-
-    // Create headers
-    let mut headers: HeaderMap = HeaderMap::new();
-
-    // Create OpenAI Api key header
-    headers.insert(
-        "authorization",
-        HeaderValue::from_str(&format!("Bearer {}", api_key))
-            .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?,
-    );
-
-    // Create OpenAI Org header
-    headers.insert(
-        "OpenAI-Organization",
-        HeaderValue::from_str(api_org.as_str())
-            .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?,
-    );
-
-    // Create client
-    let client: Client = Client::builder()
-        .default_headers(headers)
-        .build()
-        .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?;
-
-    // Create chat completion
-    let chat_completion: ChatCompletion = ChatCompletion {
-        model: "gpt-4o".to_string(),
-        messages,
-        temperature: 0.1,
-    };
-
-    // // Troubleshooting
-    // let res_raw = client
-    //   .post(url)
-    //   .json(&chat_completion)
-    //   .send()
-    //   .await
-    //   .unwrap();
-    // dbg!(res_raw.text().await.unwrap());
-
-    // Extract API Response
-    let res: APIResponse = client
-        .post(url)
-        .json(&chat_completion)
-        .send()
+pub async fn call_gpt(messages: Vec<Message>) -> Result<String, CallGptError> {
+    default_client().await?.complete(messages).await
+}
+
+/// Sends a list of messages with per-call overrides such as the model, temperature or
+/// extra request headers.
+///
+/// # Errors
+///
+/// Fails under the same conditions as [`call_gpt`], and additionally if
+/// `params.extra_headers` is invalid or tries to override the `authorization` header.
+pub async fn call_gpt_with_params(
+    messages: Vec<Message>,
+    params: &CallParams,
+) -> Result<String, CallGptError> {
+    default_client()
+        .await?
+        .complete_with(messages, params)
+        .await
+}
+
+/// Sends a list of messages with per-call overrides and returns the content together with
+/// details about how it was produced: the effective temperature and the model that
+/// answered, which differs from the requested one when a `fallback_model` was used.
+///
+/// # Errors
+///
+/// Fails under the same conditions as [`call_gpt_with_params`].
+pub async fn call_gpt_detailed(
+    messages: Vec<Message>,
+    params: &CallParams,
+) -> Result<CompletionResult, CallGptError> {
+    default_client()
+        .await?
+        .complete_detailed(messages, params)
+        .await
+}
+
+/// Sends a list of messages and returns every choice with its index, role and finish
+/// reason. See [`GptClient::complete_choices`].
+///
+/// # Errors
+///
+/// Fails under the same conditions as [`call_gpt`], except that an empty `choices` array
+/// is returned as-is.
+pub async fn call_gpt_choices(
+    messages: Vec<Message>,
+    params: &CallParams,
+) -> Result<Vec<APIChoice>, CallGptError> {
+    default_client()
+        .await?
+        .complete_choices(messages, params)
+        .await
+}
+
+/// Sends a list of messages and returns the first choice's content along with the parsed
+/// response, e.g. for its `system_fingerprint`. See [`GptClient::complete_raw`].
+///
+/// # Errors
+///
+/// Fails under the same conditions as [`call_gpt`].
+pub async fn call_gpt_raw(messages: Vec<Message>) -> Result<(String, APIResponse), CallGptError> {
+    default_client()
+        .await?
+        .complete_raw(messages, &CallParams::default())
+        .await
+}
+
+/// Checks that the `OPEN_AI_KEY` / `OPEN_AI_ORG` credentials are valid without spending
+/// any tokens. Call it at startup to turn a bad key into an immediate, clear error.
+///
+/// # Errors
+///
+/// Returns [`CallGptError::MissingEnv`] when the variables are unset and
+/// [`CallGptError::Api`] (status 401 or 403) when the API rejects them. See
+/// [`GptClient::verify_credentials`].
+pub async fn verify_credentials() -> Result<(), CallGptError> {
+    default_client().await?.verify_credentials().await
+}
+
+/// Returns the IDs of the models the `OPEN_AI_KEY` credentials can access. See
+/// [`GptClient::list_models`].
+///
+/// # Errors
+///
+/// Returns [`CallGptError::MissingEnv`] when the credentials are unset, or any error from
+/// the models request.
+pub async fn list_models() -> Result<Vec<String>, CallGptError> {
+    default_client().await?.list_models().await
+}
+
+/// Checks `text` against OpenAI's moderation endpoint before showing it to users. See
+/// [`GptClient::moderate`].
+///
+/// # Errors
+///
+/// Returns [`CallGptError::MissingEnv`] when the credentials are unset, or any error from
+/// the moderation request.
+pub async fn moderate(text: &str) -> Result<ModerationResult, CallGptError> {
+    default_client().await?.moderate(text).await
+}
+
+/// Embeds each of `inputs` with `model`. See [`GptClient::embeddings`].
+///
+/// # Errors
+///
+/// Returns [`CallGptError::MissingEnv`] when the credentials are unset, or any error from
+/// the embeddings request.
+pub async fn call_embeddings(
+    inputs: Vec<String>,
+    model: &str,
+) -> Result<Vec<Vec<f32>>, CallGptError> {
+    default_client().await?.embeddings(inputs, model).await
+}
+
+/// Embeds `text` in overlapping chunks of at most `chunk_tokens` estimated tokens,
+/// returning each chunk's vector with its byte range. See
+/// [`GptClient::embeddings_chunked`].
+///
+/// # Errors
+///
+/// Returns [`CallGptError::MissingEnv`] when the credentials are unset, or any error from
+/// the embeddings request.
+pub async fn call_embeddings_chunked(
+    text: &str,
+    model: &str,
+    chunk_tokens: usize,
+) -> Result<Vec<EmbeddedChunk>, CallGptError> {
+    default_client()
+        .await?
+        .embeddings_chunked(text, model, chunk_tokens)
         .await
-        .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?
-        .json()
+}
+
+/// Streams the completion for a list of messages, yielding content deltas as they arrive.
+///
+/// Each item on the returned channel is the next piece of content or the error that ended
+/// the stream; the channel closes when the completion is finished. See
+/// [`GptClient::complete_stream`].
+///
+/// # Errors
+///
+/// Fails under the same conditions as [`call_gpt`] for errors detected before the stream
+/// starts.
+pub async fn call_gpt_stream(
+    messages: Vec<Message>,
+) -> Result<mpsc::Receiver<Result<String, CallGptError>>, CallGptError> {
+    default_client()
+        .await?
+        .complete_stream(messages, &CallParams::default())
+        .await
+}
+
+/// Streams the completion for a list of messages, calling `on_token` with each content
+/// delta, and returns the assembled content. See [`GptClient::complete_stream_with`].
+///
+/// # Errors
+///
+/// Fails under the same conditions as [`call_gpt_stream`], or with the error that ended
+/// the stream.
+pub async fn call_gpt_stream_with(
+    messages: Vec<Message>,
+    on_token: impl FnMut(&str),
+) -> Result<String, CallGptError> {
+    default_client()
+        .await?
+        .complete_stream_with(messages, &CallParams::default(), on_token)
         .await
-        .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?;
+}
 
-    // Send Response
-    Ok(res.choices[0].message.content.clone())
+/// Streams the completion with per-call overrides like [`call_gpt_stream_with`], also
+/// returning the token usage when `params.include_usage` is set. See
+/// [`GptClient::complete_stream_detailed`].
+///
+/// # Errors
+///
+/// Fails under the same conditions as [`call_gpt_stream_with`].
+pub async fn call_gpt_stream_detailed(
+    messages: Vec<Message>,
+    params: &CallParams,
+    on_token: impl FnMut(&str),
+) -> Result<StreamedCompletion, CallGptError> {
+    default_client()
+        .await?
+        .complete_stream_detailed(messages, params, on_token)
+        .await
+}
+
+/// Sends a list of messages using an injected HTTP client and API base URL.
+///
+/// Behaves like [`call_gpt`] but lets the caller reuse a client and point the request at
+/// any OpenAI-compatible server, such as a mock in tests. `base_url` excludes the
+/// `/chat/completions` path, e.g. [`OPENAI_BASE_URL`]. Only the credentials are read from
+/// the environment (see
+/// [`GptConfig::credentials_from_env`](crate::apis::client::GptConfig::credentials_from_env)),
+/// so optional settings such as `OPEN_AI_MODEL` or `OPEN_AI_REPLAY` do not change the
+/// request.
+///
+/// # Errors
+///
+/// Fails under the same conditions as [`call_gpt`].
+pub async fn call_gpt_with_client(
+    client: &Client,
+    base_url: &str,
+    messages: Vec<Message>,
+) -> Result<String, CallGptError> {
+    env_client(client, base_url)?.complete(messages).await
+}
+
+/// Asynchronously sends a fully specified chat completion request to the OpenAI GPT API.
+///
+/// Unlike [`call_gpt`], the caller controls the model and temperature. This is the entry
+/// point for requests loaded from a prompt file via
+/// [`load_prompt_file`](crate::helpers::prompt_file::load_prompt_file).
+///
+/// # Arguments
+///
+/// * `chat_completion` - The request body to send.
+///
+/// # Errors
+///
+/// Fails under the same conditions as [`call_gpt`].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use rust_autogpt::apis::call_request::call_completion;
+/// use rust_autogpt::helpers::prompt_file::load_prompt_file;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let prompt = load_prompt_file("prompts/summarize.json").expect("invalid prompt file");
+///
+///     match call_completion(prompt.to_chat_completion()).await {
+///         Ok(response) => println!("Response: {}", response),
+///         Err(e) => eprintln!("Error: {}", e),
+///     }
+/// }
+/// ```
+pub async fn call_completion(chat_completion: ChatCompletion) -> Result<String, CallGptError> {
+    default_client()
+        .await?
+        .complete_request(&chat_completion)
+        .await
+}
+
+/// Sends a fully specified chat completion request using an injected HTTP client and API
+/// base URL. See [`call_gpt_with_client`].
+///
+/// # Errors
+///
+/// Fails under the same conditions as [`call_gpt`].
+pub async fn call_completion_with_client(
+    client: &Client,
+    base_url: &str,
+    chat_completion: ChatCompletion,
+) -> Result<String, CallGptError> {
+    env_client(client, base_url)?
+        .complete_request(&chat_completion)
+        .await
+}
+
+// Client shared by the free functions, built from the environment on first use
+static DEFAULT_CLIENT: OnceCell<GptClient> = OnceCell::const_new();
+
+async fn default_client() -> Result<&'static GptClient, CallGptError> {
+    DEFAULT_CLIENT
+        .get_or_try_init(|| async { GptClient::new(GptConfig::from_env()?) })
+        .await
+}
+
+// Wrap an injected HTTP client with credentials read from the environment
+fn env_client(client: &Client, base_url: &str) -> Result<GptClient, CallGptError> {
+    let mut config: GptConfig = GptConfig::credentials_from_env()?;
+    config.base_url = base_url.to_string();
+    Ok(GptClient::with_http_client(config, client.clone()))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::apis::test_support::user_messages;
+    use serde_json::json;
+    use std::env;
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    // Point the env lookups at fake credentials and start a mock OpenAI server
+    async fn mock_openai() -> MockServer {
+        env::set_var("OPEN_AI_KEY", "sk-test");
+        env::set_var("OPEN_AI_ORG", "org-test");
+        MockServer::start().await
+    }
 
     #[tokio::test]
     async fn tests_call_to_openai() {
-        let message: Message = Message {
-            role: "user".to_string(),
-            content: "Hi there, this is a test. Give me a short response.".to_string(),
-        };
+        let server: MockServer = mock_openai().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{ "choices": [{ "message": { "role": "assistant", "content": "Hi!" } }] }"#,
+            ))
+            .mount(&server)
+            .await;
+
+        let message: Message = Message::new(
+            "user",
+            "Hi there, this is a test. Give me a short response.",
+        );
 
         let messages: Vec<Message> = vec![message];
 
-        let res: Result<String, Box<dyn std::error::Error + Send>> = call_gpt(messages).await;
+        let res: Result<String, CallGptError> =
+            call_gpt_with_client(&Client::new(), &server.uri(), messages).await;
         match res {
             Ok(res_str) => {
-                dbg!(res_str);
-                assert!(true);
+                assert_eq!(res_str, "Hi!");
             }
-            Err(_) => {
-                assert!(false);
+            Err(e) => {
+                panic!("call to OpenAI failed: {}", e);
             }
         }
     }
+
+    // Mount a canned response for the completions endpoint
+    async fn respond_with(server: &MockServer, template: ResponseTemplate) {
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(template)
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn tests_call_gpt_sends_headers_and_body() {
+        let server: MockServer = mock_openai().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(header("authorization", "Bearer sk-test"))
+            .and(header("OpenAI-Organization", "org-test"))
+            .and(body_partial_json(json!({
+                "model": DEFAULT_MODEL,
+                "messages": [{ "role": "user", "content": "Hello" }]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{ "message": { "role": "assistant", "content": "World" } }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let res: String = call_gpt_with_client(&Client::new(), &server.uri(), user_messages())
+            .await
+            .unwrap();
+
+        assert_eq!(res, "World");
+    }
+
+    #[tokio::test]
+    async fn tests_call_gpt_rate_limited() {
+        let server: MockServer = mock_openai().await;
+        respond_with(
+            &server,
+            ResponseTemplate::new(429).set_body_json(json!({
+                "error": { "message": "Rate limit reached", "type": "requests", "code": "rate_limit_exceeded" }
+            })),
+        )
+        .await;
+
+        let err: CallGptError =
+            call_gpt_with_client(&Client::new(), &server.uri(), user_messages())
+                .await
+                .unwrap_err();
+
+        match err {
+            CallGptError::Api {
+                status,
+                code,
+                message,
+            } => {
+                assert_eq!(status, 429);
+                assert_eq!(code.as_deref(), Some("rate_limit_exceeded"));
+                assert_eq!(message, "Rate limit reached");
+            }
+            other => panic!("expected an API error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn tests_call_gpt_bad_request() {
+        let server: MockServer = mock_openai().await;
+        respond_with(
+            &server,
+            ResponseTemplate::new(400).set_body_json(json!({
+                "error": { "message": "Invalid value for 'temperature'", "type": "invalid_request_error", "param": "temperature", "code": null }
+            })),
+        )
+        .await;
+
+        let err: CallGptError =
+            call_gpt_with_client(&Client::new(), &server.uri(), user_messages())
+                .await
+                .unwrap_err();
+
+        assert!(matches!(
+            err,
+            CallGptError::Api { status: 400, code: None, ref message } if message.contains("temperature")
+        ));
+    }
+
+    #[tokio::test]
+    async fn tests_call_gpt_empty_choices() {
+        let server: MockServer = mock_openai().await;
+        respond_with(
+            &server,
+            ResponseTemplate::new(200).set_body_json(json!({ "choices": [] })),
+        )
+        .await;
+
+        let err: CallGptError =
+            call_gpt_with_client(&Client::new(), &server.uri(), user_messages())
+                .await
+                .unwrap_err();
+
+        assert!(matches!(err, CallGptError::EmptyChoices));
+    }
+
+    #[tokio::test]
+    async fn tests_call_gpt_malformed_body() {
+        let server: MockServer = mock_openai().await;
+        respond_with(
+            &server,
+            ResponseTemplate::new(200).set_body_string("<html>gateway</html>"),
+        )
+        .await;
+
+        let err: CallGptError =
+            call_gpt_with_client(&Client::new(), &server.uri(), user_messages())
+                .await
+                .unwrap_err();
+
+        assert!(
+            matches!(err, CallGptError::Deserialize { ref body, .. } if body.contains("gateway"))
+        );
+    }
 }
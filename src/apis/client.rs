@@ -0,0 +1,2834 @@
+//! A reusable, explicitly configured handle to the OpenAI chat completions API.
+//!
+//! `GptClient` owns a pooled `reqwest::Client` and the credentials and defaults it sends
+//! with every request, so configuration is read once instead of on every call. The free
+//! functions in [`call_request`](crate::apis::call_request) are thin wrappers around it.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use rust_autogpt::apis::client::{GptClient, GptConfig};
+//! use rust_autogpt::models::general::llm::Message;
+//! use std::time::Duration;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let mut config = GptConfig::from_env().expect("missing OpenAI credentials");
+//!     config.model = "gpt-4o-mini".to_string();
+//!     config.timeout = Some(Duration::from_secs(30));
+//!     let client = GptClient::new(config).expect("failed to build client");
+//!
+//!     let messages = vec![Message::new("user", "Hello, how are you?")];
+//!     match client.complete(messages).await {
+//!         Ok(response) => println!("Response: {}", response),
+//!         Err(e) => eprintln!("Error: {}", e),
+//!     }
+//! }
+//! ```
+use crate::apis::call_request::{DEFAULT_MODEL, DEFAULT_TEMPERATURE, OPENAI_BASE_URL};
+use crate::apis::concurrency::{acquire_request_permit, ConcurrencyLimit};
+use crate::apis::error::CallGptError;
+use crate::apis::history::{
+    enforce_history_limit, merge_system_messages, DEFAULT_MAX_HISTORY_MESSAGES,
+};
+use crate::apis::params::{CallParams, MinLength};
+use crate::apis::replay::{Recorder, ReplayMode};
+use crate::apis::single_flight::SingleFlight;
+use crate::apis::stream::DeltaDecoder;
+use crate::helpers::fingerprint::request_fingerprint;
+use crate::helpers::model_name::normalize_model;
+use crate::helpers::redact::redact;
+use crate::helpers::retry::{retry_with_backoff, RetryPolicy};
+use crate::helpers::tokens::split_into_token_chunks;
+use crate::models::general::llm::{
+    APIChoice, APIEmbeddingResponse, APIModelList, APIResponse, APIUsage, ChatCompletion,
+    CompletionResult, EmbeddedChunk, EmbeddingRequest, Message, ModerationRequest,
+    ModerationResponse, ModerationResult, RateLimits, ReasoningEffort, StreamOptions,
+    StreamedCompletion, StreamingChatCompletion,
+};
+use chrono::format::{Item, StrftimeItems};
+use chrono::Utc;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, USER_AGENT};
+use reqwest::{Client, Request, RequestBuilder, Response, StatusCode};
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Write;
+use std::ops::Range;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter, EnumString};
+use tokio::sync::{mpsc, oneshot, OwnedSemaphorePermit};
+use tracing::{debug, info, trace, warn, Level};
+
+const ELABORATE_INSTRUCTION: &str = "That answer is too short. Answer again in full, \
+with more detail, as a complete replacement for your previous answer.";
+
+// Content deltas buffered between the stream reader and its consumer
+const STREAM_CHANNEL_CAPACITY: usize = 64;
+
+// Distinct requests whose system fingerprint is remembered for change detection
+const MAX_TRACKED_FINGERPRINTS: usize = 1024;
+
+// Request fingerprint -> system fingerprint of the last response to it
+static SYSTEM_FINGERPRINTS: LazyLock<Mutex<HashMap<String, String>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Default [`GptConfig::pool_idle_timeout`]: 90 seconds, matching `reqwest`.
+pub const DEFAULT_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Default [`GptConfig::max_response_bytes`]: 50 MB.
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 50 * 1024 * 1024;
+
+/// [`GptConfig::datetime_format`] used when `OPEN_AI_DATETIME_CONTEXT` is enabled
+/// without `OPEN_AI_DATETIME_FORMAT`, e.g. `2025-01-31 14:05 UTC`.
+pub const DEFAULT_DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M UTC";
+
+/// `User-Agent` sent when none is configured, e.g. `rust-autogpt/0.1.0`.
+pub const DEFAULT_USER_AGENT: &str = concat!("rust-autogpt/", env!("CARGO_PKG_VERSION"));
+
+/// Data-residency region whose OpenAI endpoint requests are sent to, selected with
+/// `OPEN_AI_REGION`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, Display, EnumIter)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum Region {
+    /// The default global endpoint, `https://api.openai.com/v1`.
+    Us,
+    /// The European data-residency endpoint, `https://eu.api.openai.com/v1`.
+    Eu,
+}
+
+impl Region {
+    /// API base URL for the region.
+    pub fn base_url(self) -> &'static str {
+        match self {
+            Region::Us => OPENAI_BASE_URL,
+            Region::Eu => "https://eu.api.openai.com/v1",
+        }
+    }
+}
+
+/// Settings used to build a [`GptClient`].
+#[derive(Debug, Clone)]
+pub struct GptConfig {
+    /// Model used by [`GptClient::complete`].
+    pub model: String,
+    /// API key sent as a bearer token.
+    pub api_key: String,
+    /// Organization ID sent in the `OpenAI-Organization` header.
+    pub api_org: String,
+    /// Project ID sent in the `OpenAI-Project` header. Omitted when `None`.
+    pub api_project: Option<String>,
+    /// API base URL, excluding the `/chat/completions` path.
+    pub base_url: String,
+    /// Overall timeout for each request. `None` waits indefinitely.
+    pub timeout: Option<Duration>,
+    /// Most idle connections kept open per host. Defaults to no limit, which suits most
+    /// workloads; lower it to cap open sockets under heavy concurrency.
+    pub pool_max_idle_per_host: usize,
+    /// How long an idle pooled connection is kept before it is closed. Defaults to
+    /// [`DEFAULT_POOL_IDLE_TIMEOUT`]; `None` keeps idle connections indefinitely.
+    pub pool_idle_timeout: Option<Duration>,
+    /// Whether to send `Accept-Encoding: gzip` and transparently decompress gzip
+    /// responses, which shortens transfers of large completions. Defaults to `true`;
+    /// turn it off for proxies that mishandle compressed bodies.
+    pub gzip: bool,
+    /// Accept TLS certificates that fail verification, e.g. the self-signed certificate
+    /// of a local OpenAI-compatible server. This disables protection against
+    /// man-in-the-middle attacks, so it defaults to `false`, is logged as a warning when
+    /// the client is built, and must never be enabled in production. Only
+    /// [`GptClient::new`] applies it; an HTTP client passed to
+    /// [`GptClient::with_http_client`] must be built with the option itself.
+    pub danger_accept_invalid_certs: bool,
+    /// `User-Agent` header sent with every request.
+    pub user_agent: String,
+    /// Value of the `OpenAI-Beta` header, e.g. `assistants=v2`. Omitted when `None`.
+    pub beta: Option<String>,
+    /// Largest response body read before the request fails with
+    /// [`CallGptError::ResponseTooLarge`], guarding against endpoints that send enormous
+    /// bodies. Streamed completions are consumed incrementally, so for them the limit
+    /// applies to each event rather than to the whole body.
+    pub max_response_bytes: usize,
+    /// Longest a streamed completion may go without receiving any bytes before it is
+    /// aborted with [`CallGptError::StreamIdle`]. This catches streams that stay
+    /// connected but fall silent, independently of the overall
+    /// [`timeout`](Self::timeout). `None`, the default, waits indefinitely.
+    pub stream_idle_timeout: Option<Duration>,
+    /// Sampling temperature for every call that does not set [`CallParams::temperature`],
+    /// taking precedence over [`model_temperatures`](Self::model_temperatures). See
+    /// [`GptClient::with_temperature`].
+    pub temperature: Option<f32>,
+    /// Default sampling temperature per model, used when neither the call nor
+    /// [`temperature`](Self::temperature) sets one. Models not listed use
+    /// [`DEFAULT_TEMPERATURE`].
+    pub model_temperatures: HashMap<String, f32>,
+    /// How requests that fail with a retryable error
+    /// ([`CallGptError::is_retryable`]) are retried. Streamed completions are not
+    /// retried. Defaults to [`RetryPolicy::none`].
+    pub retry: RetryPolicy,
+    /// Records chat completion responses to, or replays them from, a JSONL file for
+    /// deterministic tests. `None` sends every request to the API.
+    pub replay: Option<ReplayMode>,
+    /// Cap on this client's requests in flight, which clients given clones of the same
+    /// limit share. `None` counts requests against the process-wide limit set with
+    /// [`set_max_concurrent_requests`](crate::apis::concurrency::set_max_concurrent_requests).
+    pub concurrency_limit: Option<ConcurrencyLimit>,
+    /// Operator-defined system prompt, e.g. safety guardrails, prepended to the
+    /// conversation of every completion the client builds. It is merged ahead of any
+    /// system messages the caller sends. Requests passed whole to
+    /// [`GptClient::complete_request`] are sent exactly as given.
+    pub system_preamble: Option<String>,
+    /// Seed sent with every chat completion that does not set [`CallParams::seed`], so a
+    /// run can be repeated with the same sampling. Compare the returned
+    /// [`CompletionResult::system_fingerprint`] to tell whether the backend changed
+    /// between runs.
+    pub seed: Option<u64>,
+    /// When set, a system message with the current UTC date and time, rendered with
+    /// this `strftime` format (see [`DEFAULT_DATETIME_FORMAT`]), is added after the
+    /// [`system_preamble`](Self::system_preamble) of every completion the client builds.
+    /// `None`, the default, adds nothing. Enabling it makes otherwise identical requests
+    /// differ over time, so they stop sharing single-flight calls and replay entries.
+    pub datetime_format: Option<String>,
+    /// Most messages a conversation may hold when it is sent. A longer one has its older
+    /// turns summarized first (see [`enforce_history_limit`]), which costs an extra call
+    /// each time, so callers keeping a long history should store the trimmed result of
+    /// `enforce_history_limit` themselves. Requests passed whole to
+    /// [`GptClient::complete_request`] are not checked.
+    pub max_history_messages: usize,
+}
+
+impl GptConfig {
+    /// Creates a configuration with the given credentials and the crate defaults for
+    /// everything else.
+    pub fn new(api_key: impl Into<String>, api_org: impl Into<String>) -> Self {
+        GptConfig {
+            model: DEFAULT_MODEL.to_string(),
+            api_key: api_key.into(),
+            api_org: api_org.into(),
+            api_project: None,
+            base_url: OPENAI_BASE_URL.to_string(),
+            timeout: None,
+            pool_max_idle_per_host: usize::MAX,
+            pool_idle_timeout: Some(DEFAULT_POOL_IDLE_TIMEOUT),
+            gzip: true,
+            danger_accept_invalid_certs: false,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            beta: None,
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            stream_idle_timeout: None,
+            temperature: None,
+            model_temperatures: HashMap::new(),
+            retry: RetryPolicy::none(),
+            replay: None,
+            system_preamble: None,
+            seed: None,
+            datetime_format: None,
+            concurrency_limit: None,
+            max_history_messages: DEFAULT_MAX_HISTORY_MESSAGES,
+        }
+    }
+
+    /// Reads only the credentials, from `OPEN_AI_KEY` and `OPEN_AI_ORG`, and uses the
+    /// crate defaults for everything else, ignoring the optional variables read by
+    /// [`from_env`](Self::from_env). With the default `dotenv` feature a `.env` file is
+    /// loaded first.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CallGptError::MissingEnv`] when either variable is unset.
+    pub fn credentials_from_env() -> Result<Self, CallGptError> {
+        #[cfg(feature = "dotenv")]
+        dotenv::dotenv().ok();
+
+        // Extract API Key information
+        let api_key: String =
+            env::var("OPEN_AI_KEY").map_err(|_| CallGptError::MissingEnv("OPEN_AI_KEY"))?;
+        let api_org: String =
+            env::var("OPEN_AI_ORG").map_err(|_| CallGptError::MissingEnv("OPEN_AI_ORG"))?;
+        Ok(GptConfig::new(api_key, api_org))
+    }
+
+    /// Reads the credentials from `OPEN_AI_KEY` and `OPEN_AI_ORG`, and any of these
+    /// optional overrides, using the crate defaults for everything else:
+    ///
+    /// - `OPEN_AI_PROJECT`: [`api_project`](Self::api_project).
+    /// - `OPEN_AI_REGION`: a [`Region`] whose endpoint becomes the `base_url`.
+    /// - `OPEN_AI_MODEL`: [`model`](Self::model).
+    /// - `OPEN_AI_USER_AGENT`: [`user_agent`](Self::user_agent).
+    /// - `OPEN_AI_BETA`: [`beta`](Self::beta).
+    /// - `OPEN_AI_MODEL_TEMPERATURES`: per-model temperatures, e.g.
+    ///   `gpt-4o=0.7,gpt-4o-mini=0.2`.
+    /// - `OPEN_AI_MAX_RETRIES`: retries after the first attempt.
+    /// - `OPEN_AI_POOL_MAX_IDLE_PER_HOST` and `OPEN_AI_POOL_IDLE_TIMEOUT_SECS`: the
+    ///   connection pool settings.
+    /// - `OPEN_AI_STREAM_IDLE_TIMEOUT_SECS`:
+    ///   [`stream_idle_timeout`](Self::stream_idle_timeout).
+    /// - `OPEN_AI_GZIP`: `0`, `false` or `off` disables [`gzip`](Self::gzip).
+    /// - `OPEN_AI_DANGER_ACCEPT_INVALID_CERTS`: `1`, `true` or `on` enables
+    ///   [`danger_accept_invalid_certs`](Self::danger_accept_invalid_certs).
+    /// - `OPEN_AI_REPLAY` or `OPEN_AI_RECORD`: a recording path to replay from or record
+    ///   to; replay wins if both are set.
+    /// - `OPEN_AI_SYSTEM_PREAMBLE_FILE` or `OPEN_AI_SYSTEM_PREAMBLE`: the
+    ///   [`system_preamble`](Self::system_preamble); the file wins if both are set.
+    /// - `OPEN_AI_SEED`: [`seed`](Self::seed).
+    /// - `OPEN_AI_DATETIME_CONTEXT`: `1`, `true` or `on` enables
+    ///   [`datetime_format`](Self::datetime_format), in the format given by
+    ///   `OPEN_AI_DATETIME_FORMAT` or [`DEFAULT_DATETIME_FORMAT`].
+    ///
+    /// With the default `dotenv` feature a `.env` file is loaded first; without it only
+    /// the process environment is consulted.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CallGptError::MissingEnv`] when either variable is unset, and
+    /// [`CallGptError::InvalidEnv`] when `OPEN_AI_REGION` names an unknown [`Region`] or
+    /// `OPEN_AI_DATETIME_FORMAT` is not a valid `strftime` format, and
+    /// [`CallGptError::Io`] when `OPEN_AI_SYSTEM_PREAMBLE_FILE` cannot be read.
+    pub fn from_env() -> Result<Self, CallGptError> {
+        let mut config: GptConfig = GptConfig::credentials_from_env()?;
+        config.api_project = env::var("OPEN_AI_PROJECT")
+            .ok()
+            .filter(|project| !project.trim().is_empty());
+        if let Some(region) = env::var("OPEN_AI_REGION")
+            .ok()
+            .filter(|region| !region.trim().is_empty())
+        {
+            let region: Region = region
+                .trim()
+                .parse()
+                .map_err(|_| CallGptError::InvalidEnv {
+                    name: "OPEN_AI_REGION",
+                    value: region.clone(),
+                    expected: Region::iter()
+                        .map(|region| region.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", "),
+                })?;
+            config.base_url = region.base_url().to_string();
+        }
+        if let Some(model) = env::var("OPEN_AI_MODEL")
+            .ok()
+            .filter(|m| !m.trim().is_empty())
+        {
+            config.model = model;
+        }
+        if let Ok(user_agent) = env::var("OPEN_AI_USER_AGENT") {
+            config.user_agent = user_agent;
+        }
+        config.beta = env::var("OPEN_AI_BETA")
+            .ok()
+            .filter(|beta| !beta.is_empty());
+        if let Ok(temperatures) = env::var("OPEN_AI_MODEL_TEMPERATURES") {
+            config.model_temperatures = parse_model_temperatures(&temperatures);
+        }
+        if let Some(retries) = env::var("OPEN_AI_MAX_RETRIES")
+            .ok()
+            .and_then(|retries| retries.trim().parse::<u32>().ok())
+        {
+            config.retry = RetryPolicy {
+                max_attempts: retries.saturating_add(1),
+                ..RetryPolicy::default()
+            };
+        }
+        if let Some(max_idle) = env::var("OPEN_AI_POOL_MAX_IDLE_PER_HOST")
+            .ok()
+            .and_then(|max_idle| max_idle.trim().parse().ok())
+        {
+            config.pool_max_idle_per_host = max_idle;
+        }
+        if let Some(secs) = env::var("OPEN_AI_POOL_IDLE_TIMEOUT_SECS")
+            .ok()
+            .and_then(|secs| secs.trim().parse().ok())
+        {
+            config.pool_idle_timeout = Some(Duration::from_secs(secs));
+        }
+        if let Some(secs) = env::var("OPEN_AI_STREAM_IDLE_TIMEOUT_SECS")
+            .ok()
+            .and_then(|secs| secs.trim().parse().ok())
+        {
+            config.stream_idle_timeout = Some(Duration::from_secs(secs));
+        }
+        if let Ok(gzip) = env::var("OPEN_AI_GZIP") {
+            config.gzip = !matches!(gzip.trim(), "0" | "false" | "off");
+        }
+        if let Ok(accept) = env::var("OPEN_AI_DANGER_ACCEPT_INVALID_CERTS") {
+            config.danger_accept_invalid_certs = matches!(accept.trim(), "1" | "true" | "on");
+        }
+        let path = |name: &str| env::var_os(name).filter(|path| !path.is_empty());
+        config.replay = match (path("OPEN_AI_REPLAY"), path("OPEN_AI_RECORD")) {
+            (Some(replay), _) => Some(ReplayMode::Replay(replay.into())),
+            (None, Some(record)) => Some(ReplayMode::Record(record.into())),
+            (None, None) => None,
+        };
+        config.system_preamble = match path("OPEN_AI_SYSTEM_PREAMBLE_FILE") {
+            Some(file) => Some(std::fs::read_to_string(file)?),
+            None => env::var("OPEN_AI_SYSTEM_PREAMBLE").ok(),
+        }
+        .filter(|preamble| !preamble.trim().is_empty());
+        config.seed = env::var("OPEN_AI_SEED")
+            .ok()
+            .and_then(|seed| seed.trim().parse().ok());
+        if let Some(seed) = config.seed {
+            info!(seed, "using sampling seed from OPEN_AI_SEED");
+        }
+        if env::var("OPEN_AI_DATETIME_CONTEXT")
+            .is_ok_and(|enabled| matches!(enabled.trim(), "1" | "true" | "on"))
+        {
+            let format: String = env::var("OPEN_AI_DATETIME_FORMAT")
+                .ok()
+                .filter(|format| !format.trim().is_empty())
+                .unwrap_or_else(|| DEFAULT_DATETIME_FORMAT.to_string());
+            if StrftimeItems::new(&format).any(|item| item == Item::Error) {
+                return Err(CallGptError::InvalidEnv {
+                    name: "OPEN_AI_DATETIME_FORMAT",
+                    value: format,
+                    expected: "a strftime format, e.g. %Y-%m-%d %H:%M UTC".to_string(),
+                });
+            }
+            config.datetime_format = Some(format);
+        }
+        Ok(config)
+    }
+}
+
+/// A configured OpenAI client. Cloning is cheap and shares the connection pool.
+///
+/// Concurrent chat completions with identical requests (same [`request_fingerprint`])
+/// and headers, made through one client or its clones, share a single in-flight request
+/// and all receive its result. If it fails, only one caller gets the original error;
+/// the rest get a [`CallGptError::SharedFailure`].
+#[derive(Debug, Clone)]
+pub struct GptClient {
+    config: GptConfig,
+    http: Client,
+    recorder: Option<Arc<Recorder>>,
+    in_flight: Arc<SingleFlight<(String, Option<RateLimits>)>>,
+    rate_limits: Arc<Mutex<Option<RateLimits>>>,
+}
+
+impl GptClient {
+    /// Builds a client, including its connection pool, from `config`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CallGptError::Http`] if the underlying HTTP client cannot be built.
+    pub fn new(config: GptConfig) -> Result<Self, CallGptError> {
+        let mut builder = Client::builder()
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .pool_idle_timeout(config.pool_idle_timeout)
+            .gzip(config.gzip);
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if config.danger_accept_invalid_certs {
+            warn!(
+                base_url = %config.base_url,
+                "TLS certificate verification is DISABLED; never use this in production"
+            );
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+
+        Ok(GptClient::wrap(config, builder.build()?))
+    }
+
+    /// Wraps an existing HTTP client, e.g. one shared with the rest of an application.
+    /// `config.timeout`, `config.gzip`, `config.danger_accept_invalid_certs` and the pool
+    /// settings are ignored in favor of the client's own settings, with a warning when
+    /// `danger_accept_invalid_certs` is set.
+    pub fn with_http_client(config: GptConfig, http: Client) -> Self {
+        if config.danger_accept_invalid_certs {
+            warn!(
+                "danger_accept_invalid_certs is ignored for an injected HTTP client; \
+                 configure certificate verification on that client instead"
+            );
+        }
+        GptClient::wrap(config, http)
+    }
+
+    fn wrap(config: GptConfig, http: Client) -> Self {
+        let recorder: Option<Arc<Recorder>> = config
+            .replay
+            .clone()
+            .map(|mode| Arc::new(Recorder::new(mode)));
+        GptClient {
+            config,
+            http,
+            recorder,
+            in_flight: Arc::default(),
+            rate_limits: Arc::default(),
+        }
+    }
+
+    /// Returns the configuration the client was built with.
+    pub fn config(&self) -> &GptConfig {
+        &self.config
+    }
+
+    /// Returns the rate-limit headers of the most recent successful response received by
+    /// this client or its clones, so callers can throttle before the limits run out.
+    /// `None` until a response carrying them has arrived. Under concurrent requests this
+    /// may belong to any of them; [`CompletionResult::rate_limits`] holds those of the
+    /// call's own response.
+    pub fn rate_limits(&self) -> Option<RateLimits> {
+        *self.rate_limits.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns a clone of the client that uses `model` by default. The clone shares the
+    /// connection pool, and [`CallParams::model`] still wins for individual calls.
+    pub fn with_model(&self, model: impl Into<String>) -> Self {
+        let mut client: GptClient = self.clone();
+        client.config.model = model.into();
+        client
+    }
+
+    /// Returns a clone of the client that uses `temperature` by default, for every model.
+    /// The clone shares the connection pool, and [`CallParams::temperature`] still wins for
+    /// individual calls.
+    pub fn with_temperature(&self, temperature: f32) -> Self {
+        let mut client: GptClient = self.clone();
+        client.config.temperature = Some(temperature);
+        client
+    }
+
+    /// Sends `messages` using the configured model and the default temperature and
+    /// returns the content of the first choice.
+    ///
+    /// # Errors
+    ///
+    /// See [`call_gpt`](crate::apis::call_request::call_gpt).
+    pub async fn complete(&self, messages: Vec<Message>) -> Result<String, CallGptError> {
+        self.complete_with(messages, &CallParams::default()).await
+    }
+
+    /// Sends `messages` with per-call overrides and returns the content of the first choice.
+    ///
+    /// # Errors
+    ///
+    /// See [`call_gpt`](crate::apis::call_request::call_gpt). Additionally returns
+    /// [`CallGptError::ForbiddenHeader`] if `params.extra_headers` tries to set
+    /// `authorization`.
+    pub async fn complete_with(
+        &self,
+        messages: Vec<Message>,
+        params: &CallParams,
+    ) -> Result<String, CallGptError> {
+        Ok(self.complete_detailed(messages, params).await?.content)
+    }
+
+    /// Like [`complete_with`](Self::complete_with), but also reports the effective model
+    /// and temperature that produced the result.
+    ///
+    /// Multiple leading system messages are merged into one before sending (see
+    /// [`merge_system_messages`]).
+    ///
+    /// When `params.prefill` is set it is appended as an assistant message and the
+    /// returned content starts with the prefill followed by the model's continuation.
+    ///
+    /// If the model still fails with a retryable error (rate limiting, server errors,
+    /// timeouts) after the configured [`GptConfig::retry`] attempts and
+    /// `params.fallback_model` is set, the request is sent with the fallback model, under
+    /// the same retry policy, and a warning is logged.
+    ///
+    /// When `params.min_length` is set and the reply is shorter, the reply is sent back
+    /// with a request to elaborate, up to `retries` times. Each elaboration is a complete
+    /// answer that replaces the previous one rather than being appended to it, and the
+    /// longest reply received is returned. A prefill is sent again after each request to
+    /// elaborate and starts every reply, so lengths are compared with it included. A
+    /// failed re-prompt is logged and ends the retries early.
+    ///
+    /// # Errors
+    ///
+    /// See [`complete_with`](Self::complete_with). When a fallback was attempted, the
+    /// fallback's error is returned.
+    pub async fn complete_detailed(
+        &self,
+        messages: Vec<Message>,
+        params: &CallParams,
+    ) -> Result<CompletionResult, CallGptError> {
+        let messages: Vec<Message> = self.within_history_limit(messages, params).await?;
+        let mut chat_completion: ChatCompletion = self.chat_completion(messages, params);
+
+        let mut result: CompletionResult = match (
+            self.send(&chat_completion, params).await,
+            &params.fallback_model,
+        ) {
+            (Err(e), Some(fallback_model)) if e.is_retryable() => {
+                warn!(
+                    model = %chat_completion.model,
+                    fallback_model = %fallback_model,
+                    error = %e,
+                    "model failed, retrying with fallback model"
+                );
+                chat_completion.model = normalize_model(fallback_model);
+                chat_completion.temperature = self.temperature(&chat_completion.model, params);
+                self.send(&chat_completion, params).await?
+            }
+            (result, _) => result?,
+        };
+
+        if let Some(prefill) = &params.prefill {
+            result.content = format!("{}{}", prefill, result.content);
+        }
+        if let Some(min_length) = params.min_length {
+            result = self
+                .elaborate(chat_completion, result, params, min_length)
+                .await;
+        }
+        Ok(result)
+    }
+
+    // Re-prompt a too-short reply, keeping the longest answer
+    async fn elaborate(
+        &self,
+        mut chat_completion: ChatCompletion,
+        mut best: CompletionResult,
+        params: &CallParams,
+        min_length: MinLength,
+    ) -> CompletionResult {
+        // Each re-prompt ends with the prefill again, so every candidate starts with it and
+        // they compare fairly. The replies themselves already include it.
+        let prefill: Option<Message> = params
+            .prefill
+            .as_ref()
+            .and_then(|_| chat_completion.messages.pop());
+
+        let mut reply: String = best.content.clone();
+        for retry in 1..=min_length.retries {
+            let chars: usize = best.content.chars().count();
+            if chars >= min_length.chars {
+                break;
+            }
+            debug!(
+                retry,
+                chars,
+                min_chars = min_length.chars,
+                "reply too short, re-prompting"
+            );
+
+            chat_completion
+                .messages
+                .push(Message::new("assistant", reply));
+            chat_completion
+                .messages
+                .push(Message::new("user", ELABORATE_INSTRUCTION));
+            chat_completion.messages.extend(prefill.clone());
+            let sent: Result<CompletionResult, CallGptError> =
+                self.send(&chat_completion, params).await;
+            if prefill.is_some() {
+                chat_completion.messages.pop();
+            }
+            let mut result: CompletionResult = match sent {
+                Ok(result) => result,
+                Err(e) => {
+                    warn!(error = %e, "re-prompt for a longer reply failed");
+                    break;
+                }
+            };
+            if let Some(prefill) = &params.prefill {
+                result.content = format!("{}{}", prefill, result.content);
+            }
+
+            reply = result.content.clone();
+            if result.content.chars().count() > best.content.chars().count() {
+                best = result;
+            }
+        }
+        best
+    }
+
+    /// Streams the completion for `messages`, yielding content deltas as they arrive.
+    ///
+    /// The request is sent, and its status checked, before this returns; the body is then
+    /// read on a background task. Each item on the returned channel is either the next
+    /// piece of content or the error that ended the stream, and the channel closes once
+    /// the stream is complete. A `params.prefill` is yielded as the first item.
+    /// `params.fallback_model` is not used when streaming.
+    ///
+    /// # Errors
+    ///
+    /// Fails under the same conditions as [`complete_with`](Self::complete_with) for
+    /// errors detected before the stream starts.
+    pub async fn complete_stream(
+        &self,
+        messages: Vec<Message>,
+        params: &CallParams,
+    ) -> Result<mpsc::Receiver<Result<String, CallGptError>>, CallGptError> {
+        let (rx, _usage) = self.open_stream(messages, params).await?;
+        Ok(rx)
+    }
+
+    /// Streams the completion for `messages`, calling `on_token` with each content delta
+    /// as it arrives, and returns the assembled content once the stream ends.
+    ///
+    /// This is [`complete_stream`](Self::complete_stream) without a channel to drain,
+    /// for simple "print as you go" use.
+    ///
+    /// # Errors
+    ///
+    /// See [`complete_stream`](Self::complete_stream). An error that ends the stream
+    /// early is returned after `on_token` has seen the content received before it.
+    pub async fn complete_stream_with(
+        &self,
+        messages: Vec<Message>,
+        params: &CallParams,
+        on_token: impl FnMut(&str),
+    ) -> Result<String, CallGptError> {
+        Ok(self
+            .complete_stream_detailed(messages, params, on_token)
+            .await?
+            .content)
+    }
+
+    /// Like [`complete_stream_with`](Self::complete_stream_with), also returning the
+    /// token usage of the stream when `params.include_usage` is set.
+    ///
+    /// # Errors
+    ///
+    /// See [`complete_stream_with`](Self::complete_stream_with).
+    pub async fn complete_stream_detailed(
+        &self,
+        messages: Vec<Message>,
+        params: &CallParams,
+        mut on_token: impl FnMut(&str),
+    ) -> Result<StreamedCompletion, CallGptError> {
+        let (mut chunks, usage) = self.open_stream(messages, params).await?;
+
+        let mut content: String = String::new();
+        while let Some(chunk) = chunks.recv().await {
+            let chunk: String = chunk?;
+            on_token(&chunk);
+            content.push_str(&chunk);
+        }
+        // Resolves once the reader task has finished with the body
+        Ok(StreamedCompletion {
+            content,
+            usage: usage.await.ok(),
+        })
+    }
+
+    // Send a streaming request; the usage, if reported, arrives once the stream ends
+    async fn open_stream(
+        &self,
+        messages: Vec<Message>,
+        params: &CallParams,
+    ) -> Result<
+        (
+            mpsc::Receiver<Result<String, CallGptError>>,
+            oneshot::Receiver<APIUsage>,
+        ),
+        CallGptError,
+    > {
+        self.require_live("streamed chat/completions")?;
+        let messages: Vec<Message> = self.within_history_limit(messages, params).await?;
+        let chat_completion: ChatCompletion = self.chat_completion(messages, params);
+        let permit: Option<OwnedSemaphorePermit> =
+            acquire_request_permit(self.config.concurrency_limit.as_ref()).await;
+        let request: RequestBuilder = self
+            .http
+            .post(self.url("chat/completions"))
+            .headers(self.headers(params)?)
+            .json(&StreamingChatCompletion {
+                request: &chat_completion,
+                stream: true,
+                stream_options: params.include_usage.then_some(StreamOptions {
+                    include_usage: true,
+                }),
+            });
+        trace_request(&request);
+        let response: Response = request.send().await?;
+
+        let status: StatusCode = response.status();
+        if !status.is_success() {
+            let body: String = self.read_body(response).await?;
+            return Err(CallGptError::api(status.as_u16(), &body));
+        }
+
+        let (tx, rx) = mpsc::channel(STREAM_CHANNEL_CAPACITY);
+        let (usage_tx, usage_rx) = oneshot::channel();
+        if let Some(prefill) = &params.prefill {
+            tx.send(Ok(prefill.clone())).await.ok();
+        }
+        let max_frame_bytes: usize = self.config.max_response_bytes;
+        let idle_timeout: Option<Duration> = self.config.stream_idle_timeout;
+        tokio::spawn(async move {
+            // Hold the permit until the whole body has been read
+            let _permit: Option<OwnedSemaphorePermit> = permit;
+            if let Some(usage) = forward_stream(response, tx, max_frame_bytes, idle_timeout).await {
+                usage_tx.send(usage).ok();
+            }
+        });
+        Ok((rx, usage_rx))
+    }
+
+    /// Sends `messages` and returns every choice in the response, with its index, role
+    /// and finish reason, instead of only the first choice's content.
+    ///
+    /// `params.fallback_model` is not used, and a `params.prefill` is sent but not
+    /// prepended to the returned content.
+    ///
+    /// # Errors
+    ///
+    /// See [`complete_with`](Self::complete_with). An empty `choices` array is returned
+    /// as-is rather than as [`CallGptError::EmptyChoices`].
+    pub async fn complete_choices(
+        &self,
+        messages: Vec<Message>,
+        params: &CallParams,
+    ) -> Result<Vec<APIChoice>, CallGptError> {
+        let messages: Vec<Message> = self.within_history_limit(messages, params).await?;
+        let chat_completion: ChatCompletion = self.chat_completion(messages, params);
+        Ok(self.response(&chat_completion, params).await?.0.choices)
+    }
+
+    /// Sends `messages` and returns the content of the first choice together with the
+    /// parsed response it came from, for callers that need fields beyond the content.
+    ///
+    /// `params.fallback_model` is not used. A `params.prefill` is prepended to the
+    /// returned content but not to the response.
+    ///
+    /// # Errors
+    ///
+    /// See [`complete_with`](Self::complete_with).
+    pub async fn complete_raw(
+        &self,
+        messages: Vec<Message>,
+        params: &CallParams,
+    ) -> Result<(String, APIResponse), CallGptError> {
+        let messages: Vec<Message> = self.within_history_limit(messages, params).await?;
+        let chat_completion: ChatCompletion = self.chat_completion(messages, params);
+        let (res, _) = self.response(&chat_completion, params).await?;
+
+        let choice: &APIChoice = res.choices.first().ok_or(CallGptError::EmptyChoices)?;
+        let content: String = format!(
+            "{}{}",
+            params.prefill.as_deref().unwrap_or_default(),
+            choice.message.content.as_deref().unwrap_or_default()
+        );
+        Ok((content, res))
+    }
+
+    /// Sends a fully specified request and returns the content of the first choice.
+    ///
+    /// # Errors
+    ///
+    /// See [`call_gpt`](crate::apis::call_request::call_gpt).
+    pub async fn complete_request(
+        &self,
+        chat_completion: &ChatCompletion,
+    ) -> Result<String, CallGptError> {
+        Ok(self
+            .send(chat_completion, &CallParams::default())
+            .await?
+            .content)
+    }
+
+    /// Checks that the configured key and organization are accepted by the API.
+    ///
+    /// Issues a single `GET /models` request, which costs no tokens, so applications can
+    /// fail fast at startup instead of on their first real request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CallGptError::Api`] with status 401 or 403 when the credentials are
+    /// rejected, or any transport error encountered while reaching the API.
+    pub async fn verify_credentials(&self) -> Result<(), CallGptError> {
+        self.require_live("models")?;
+        let request: RequestBuilder = self.http.get(self.url("models"));
+        self.execute(request, &CallParams::default()).await?;
+        Ok(())
+    }
+
+    /// Returns the IDs of the models available to the configured key, e.g. to populate
+    /// a model picker or to check a requested model exists before using it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CallGptError::Api`] when the request is rejected and
+    /// [`CallGptError::Deserialize`] when the model list cannot be parsed.
+    pub async fn list_models(&self) -> Result<Vec<String>, CallGptError> {
+        self.require_live("models")?;
+        let request: RequestBuilder = self.http.get(self.url("models"));
+        let body: String = self.execute(request, &CallParams::default()).await?;
+
+        let models: APIModelList =
+            serde_json::from_str(&body).map_err(|e| CallGptError::deserialize(e, &body))?;
+        Ok(models.data.into_iter().map(|model| model.id).collect())
+    }
+
+    /// Embeds each of `inputs` with `model`, returning the vectors in input order.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CallGptError::Api`] when the request is rejected (e.g. an input exceeds
+    /// the model's token limit) and [`CallGptError::Deserialize`] when the response cannot
+    /// be parsed.
+    pub async fn embeddings(
+        &self,
+        inputs: Vec<String>,
+        model: &str,
+    ) -> Result<Vec<Vec<f32>>, CallGptError> {
+        self.require_live("embeddings")?;
+        let request: RequestBuilder =
+            self.http
+                .post(self.url("embeddings"))
+                .json(&EmbeddingRequest {
+                    model: model.to_string(),
+                    input: inputs,
+                });
+        let body: String = self.execute(request, &CallParams::default()).await?;
+
+        let mut res: APIEmbeddingResponse =
+            serde_json::from_str(&body).map_err(|e| CallGptError::deserialize(e, &body))?;
+        res.data.sort_by_key(|embedding| embedding.index);
+        Ok(res
+            .data
+            .into_iter()
+            .map(|embedding| embedding.embedding)
+            .collect())
+    }
+
+    /// Embeds `text` in chunks of at most `chunk_tokens` estimated tokens, so inputs
+    /// longer than the model's limit (such as whole source files) can be embedded.
+    ///
+    /// Consecutive chunks overlap by a tenth of `chunk_tokens` so content that straddles a
+    /// boundary is represented in both. All chunks are sent in a single request, and each
+    /// vector is returned with the byte range of `text` it covers. Chunk sizes come from
+    /// [`estimate_tokens`](crate::helpers::tokens::estimate_tokens), so leave some headroom
+    /// below the model's real limit.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CallGptError::InvalidChunkSize`] if `chunk_tokens` is zero, otherwise see
+    /// [`embeddings`](Self::embeddings).
+    pub async fn embeddings_chunked(
+        &self,
+        text: &str,
+        model: &str,
+        chunk_tokens: usize,
+    ) -> Result<Vec<EmbeddedChunk>, CallGptError> {
+        let ranges: Vec<Range<usize>> =
+            split_into_token_chunks(text, chunk_tokens, chunk_tokens / 10)
+                .map_err(CallGptError::InvalidChunkSize)?;
+        if ranges.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let inputs: Vec<String> = ranges
+            .iter()
+            .map(|range| text[range.clone()].to_string())
+            .collect();
+        let embeddings: Vec<Vec<f32>> = self.embeddings(inputs, model).await?;
+        Ok(ranges
+            .into_iter()
+            .zip(embeddings)
+            .map(|(range, embedding)| EmbeddedChunk { range, embedding })
+            .collect())
+    }
+
+    /// Runs `text` through OpenAI's moderation endpoint, returning whether it was flagged
+    /// along with the per-category flags and scores.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CallGptError::Api`] when the request is rejected,
+    /// [`CallGptError::Deserialize`] when the response cannot be parsed and
+    /// [`CallGptError::EmptyModeration`] when it contains no results.
+    pub async fn moderate(&self, text: &str) -> Result<ModerationResult, CallGptError> {
+        self.require_live("moderations")?;
+        let request: RequestBuilder =
+            self.http
+                .post(self.url("moderations"))
+                .json(&ModerationRequest {
+                    input: text.to_string(),
+                });
+        let body: String = self.execute(request, &CallParams::default()).await?;
+
+        let res: ModerationResponse =
+            serde_json::from_str(&body).map_err(|e| CallGptError::deserialize(e, &body))?;
+        res.results
+            .into_iter()
+            .next()
+            .ok_or(CallGptError::EmptyModeration)
+    }
+
+    // Build the request body for a call, applying per-call overrides and any prefill
+    // Summarize the history with the model of the call once it exceeds the limit
+    async fn within_history_limit(
+        &self,
+        messages: Vec<Message>,
+        params: &CallParams,
+    ) -> Result<Vec<Message>, CallGptError> {
+        let model: &str = params.model.as_deref().unwrap_or(&self.config.model);
+        enforce_history_limit(self, messages, model, self.config.max_history_messages).await
+    }
+
+    pub(crate) fn chat_completion(
+        &self,
+        messages: Vec<Message>,
+        params: &CallParams,
+    ) -> ChatCompletion {
+        let preamble: Option<Message> = self
+            .config
+            .system_preamble
+            .as_ref()
+            .map(|preamble| Message::new("system", preamble.clone()));
+        let datetime: Option<Message> = self
+            .config
+            .datetime_format
+            .as_deref()
+            .and_then(current_datetime)
+            .map(|now| Message::new("system", format!("Current date and time (UTC): {}", now)));
+        let mut messages: Vec<Message> = merge_system_messages(
+            preamble
+                .into_iter()
+                .chain(datetime)
+                .chain(messages)
+                .collect(),
+        );
+        if let Some(prefill) = &params.prefill {
+            messages.push(Message::new("assistant", prefill.clone()));
+        }
+
+        // Create chat completion
+        let model: String = normalize_model(params.model.as_deref().unwrap_or(&self.config.model));
+        // Other models reject the parameter
+        let reasoning_effort: Option<ReasoningEffort> = params
+            .reasoning_effort
+            .filter(|_| is_reasoning_model(&model));
+        let (max_tokens, max_completion_tokens) = if uses_max_completion_tokens(&model) {
+            (None, params.max_output_tokens)
+        } else {
+            (params.max_output_tokens, None)
+        };
+        ChatCompletion {
+            temperature: self.temperature(&model, params),
+            model,
+            messages,
+            reasoning_effort,
+            max_tokens,
+            max_completion_tokens,
+            seed: params.seed.or(self.config.seed),
+            extra: params.extra.clone(),
+        }
+    }
+
+    // The per-call temperature, else the configured default for the model
+    fn temperature(&self, model: &str, params: &CallParams) -> f32 {
+        params
+            .temperature
+            .or(self.config.temperature)
+            .unwrap_or_else(|| {
+                self.config
+                    .model_temperatures
+                    .get(model)
+                    .copied()
+                    .unwrap_or(DEFAULT_TEMPERATURE)
+            })
+    }
+
+    async fn send(
+        &self,
+        chat_completion: &ChatCompletion,
+        params: &CallParams,
+    ) -> Result<CompletionResult, CallGptError> {
+        let (res, rate_limits) = self.response(chat_completion, params).await?;
+
+        // Send Response
+        let choice: &APIChoice = res.choices.first().ok_or(CallGptError::EmptyChoices)?;
+        Ok(CompletionResult {
+            content: choice.message.content.clone().unwrap_or_default(),
+            model: chat_completion.model.clone(),
+            temperature: chat_completion.temperature,
+            system_fingerprint: res.system_fingerprint,
+            rate_limits,
+        })
+    }
+
+    // The parsed response and its rate-limit headers, which replayed responses lack
+    async fn response(
+        &self,
+        chat_completion: &ChatCompletion,
+        params: &CallParams,
+    ) -> Result<(APIResponse, Option<RateLimits>), CallGptError> {
+        let fingerprint: String = request_fingerprint(chat_completion);
+        let (body, rate_limits) = match &self.recorder {
+            Some(recorder) if recorder.is_replay() => (recorder.replay(&fingerprint).await?, None),
+            recorder => {
+                // Concurrent identical requests share one round trip, but only when they
+                // would also send the same headers (tenant, gateway routing, ...)
+                let flight_key: String = flight_key(&fingerprint, &self.headers(params)?);
+                self.in_flight
+                    .run(&flight_key, || async {
+                        // Confirm endpoint
+                        let request: RequestBuilder = self
+                            .http
+                            .post(self.url("chat/completions"))
+                            .json(chat_completion);
+                        let (body, rate_limits) = self.execute_with_limits(request, params).await?;
+
+                        if let Some(recorder) = recorder {
+                            recorder.record(&fingerprint, &body).await?;
+                        }
+                        Ok((body, rate_limits))
+                    })
+                    .await?
+            }
+        };
+
+        // Extract API Response
+        let res: APIResponse =
+            serde_json::from_str(&body).map_err(|e| CallGptError::deserialize(e, &body))?;
+        if let Some(system_fingerprint) = &res.system_fingerprint {
+            track_system_fingerprint(chat_completion, &fingerprint, system_fingerprint);
+        }
+        Ok((res, rate_limits))
+    }
+
+    // Only chat completions are recorded, so replay cannot answer any other request
+    fn require_live(&self, endpoint: &'static str) -> Result<(), CallGptError> {
+        match &self.recorder {
+            Some(recorder) if recorder.is_replay() => {
+                Err(CallGptError::ReplayUnsupported { endpoint })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Attach the headers, send, and return the body of a successful response. The raw
+    // body is read first so a parse failure can report what came back.
+    async fn execute(
+        &self,
+        request: RequestBuilder,
+        params: &CallParams,
+    ) -> Result<String, CallGptError> {
+        Ok(self.execute_with_limits(request, params).await?.0)
+    }
+
+    // `execute`, also returning the rate-limit headers of the successful response
+    async fn execute_with_limits(
+        &self,
+        request: RequestBuilder,
+        params: &CallParams,
+    ) -> Result<(String, Option<RateLimits>), CallGptError> {
+        let request: RequestBuilder = request.headers(self.headers(params)?);
+        trace_request(&request);
+
+        retry_with_backoff(
+            &self.config.retry,
+            || {
+                let attempt: RequestBuilder = request
+                    .try_clone()
+                    .expect("JSON and empty request bodies can be cloned");
+                async move {
+                    // Released between attempts so backoff does not hold up other requests
+                    let _permit: Option<OwnedSemaphorePermit> =
+                        acquire_request_permit(self.config.concurrency_limit.as_ref()).await;
+                    let response: Response = attempt.send().await?;
+                    let status: StatusCode = response.status();
+                    let rate_limits: Option<RateLimits> = parse_rate_limits(response.headers());
+                    let body: String = self.read_body(response).await?;
+
+                    if !status.is_success() {
+                        return Err(CallGptError::api(status.as_u16(), &body));
+                    }
+                    if let Some(rate_limits) = rate_limits {
+                        debug!(
+                            remaining_requests = ?rate_limits.remaining_requests,
+                            remaining_tokens = ?rate_limits.remaining_tokens,
+                            "rate limits"
+                        );
+                        *self.rate_limits.lock().unwrap_or_else(|e| e.into_inner()) =
+                            Some(rate_limits);
+                    }
+                    Ok((body, rate_limits))
+                }
+            },
+            CallGptError::is_retryable,
+        )
+        .await
+    }
+
+    // Read the whole body, failing as soon as it grows past `max_response_bytes`
+    async fn read_body(&self, mut response: Response) -> Result<String, CallGptError> {
+        let limit: usize = self.config.max_response_bytes;
+        let too_large = || CallGptError::ResponseTooLarge { limit };
+
+        // Reject up front when the declared length is already too large
+        if response.content_length().unwrap_or(0) > limit as u64 {
+            return Err(too_large());
+        }
+
+        let mut body: Vec<u8> = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > limit {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.config.base_url.trim_end_matches('/'), path)
+    }
+
+    // Authentication headers sent with every request, plus any per-call extras
+    fn headers(&self, params: &CallParams) -> Result<HeaderMap, CallGptError> {
+        let mut headers: HeaderMap = HeaderMap::new();
+
+        // Create OpenAI Api key header
+        headers.insert(
+            "authorization",
+            HeaderValue::from_str(&format!("Bearer {}", self.config.api_key))?,
+        );
+
+        // Create OpenAI Org and Project headers, letting the call pick its tenant
+        let organization: &str = params
+            .organization
+            .as_deref()
+            .unwrap_or(&self.config.api_org);
+        headers.insert("OpenAI-Organization", HeaderValue::from_str(organization)?);
+        if let Some(project) = params
+            .project
+            .as_deref()
+            .or(self.config.api_project.as_deref())
+        {
+            headers.insert("OpenAI-Project", HeaderValue::from_str(project)?);
+        }
+
+        // Identify the crate (or the embedding application) to OpenAI
+        headers.insert(USER_AGENT, HeaderValue::from_str(&self.config.user_agent)?);
+
+        // Opt in to beta features only when asked to
+        if let Some(beta) = &self.config.beta {
+            headers.insert("OpenAI-Beta", HeaderValue::from_str(beta)?);
+        }
+
+        // Merge caller supplied headers, never letting them replace the credentials
+        for (name, value) in &params.extra_headers {
+            let name: HeaderName = HeaderName::from_bytes(name.as_bytes())?;
+            if name == AUTHORIZATION {
+                return Err(CallGptError::ForbiddenHeader(name.to_string()));
+            }
+            headers.insert(name, HeaderValue::from_str(value)?);
+        }
+
+        Ok(headers)
+    }
+}
+
+// The request fingerprint plus every header but the credentials, in a stable order
+fn flight_key(fingerprint: &str, headers: &HeaderMap) -> String {
+    let mut sent: Vec<String> = headers
+        .iter()
+        .filter(|(name, _)| *name != AUTHORIZATION)
+        .map(|(name, value)| format!("{}={}", name, String::from_utf8_lossy(value.as_bytes())))
+        .collect();
+    sent.sort();
+    format!("{}/{}", fingerprint, sent.join("\n"))
+}
+
+// Formats the current UTC time, skipping the message rather than panicking on a bad format
+fn current_datetime(format: &str) -> Option<String> {
+    let mut now: String = String::new();
+    match write!(now, "{}", Utc::now().format(format)) {
+        Ok(()) => Some(now),
+        Err(_) => {
+            warn!(
+                format,
+                "invalid datetime format, omitting date/time context"
+            );
+            None
+        }
+    }
+}
+
+// Read the `x-ratelimit-*` headers, or `None` when the response carries none of them
+fn parse_rate_limits(headers: &HeaderMap) -> Option<RateLimits> {
+    let value =
+        |name: &str| -> Option<u64> { headers.get(name)?.to_str().ok()?.trim().parse().ok() };
+    let rate_limits: RateLimits = RateLimits {
+        limit_requests: value("x-ratelimit-limit-requests"),
+        limit_tokens: value("x-ratelimit-limit-tokens"),
+        remaining_requests: value("x-ratelimit-remaining-requests"),
+        remaining_tokens: value("x-ratelimit-remaining-tokens"),
+    };
+    (rate_limits != RateLimits::default()).then_some(rate_limits)
+}
+
+// Log the exact request about to be sent, with credentials masked, at trace level
+fn trace_request(request: &RequestBuilder) {
+    if !tracing::enabled!(Level::TRACE) {
+        return;
+    }
+    if let Some(Ok(request)) = request.try_clone().map(RequestBuilder::build) {
+        trace!(request = %describe_request(&request), "sending request");
+    }
+}
+
+// Render a request as it goes over the wire: request line, headers, then the raw body
+fn describe_request(request: &Request) -> String {
+    let mut description: String = format!("{} {}\n", request.method(), request.url());
+    for (name, value) in request.headers() {
+        description.push_str(&format!(
+            "{}: {}\n",
+            name,
+            String::from_utf8_lossy(value.as_bytes())
+        ));
+    }
+    if let Some(body) = request.body().and_then(|body| body.as_bytes()) {
+        description.push('\n');
+        description.push_str(&String::from_utf8_lossy(body));
+    }
+    redact(&description)
+}
+
+// o-series models (o1, o3-mini, ...) are the ones that accept `reasoning_effort`
+fn is_reasoning_model(model: &str) -> bool {
+    let mut chars = model.chars();
+    chars.next() == Some('o') && chars.next().is_some_and(|c| c.is_ascii_digit())
+}
+
+// Newer models reject the deprecated `max_tokens` in favour of `max_completion_tokens`
+fn uses_max_completion_tokens(model: &str) -> bool {
+    is_reasoning_model(model) || model.starts_with("gpt-5")
+}
+
+// Parse `model=temperature` pairs separated by commas, skipping malformed entries
+fn parse_model_temperatures(value: &str) -> HashMap<String, f32> {
+    value
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .filter_map(|entry| {
+            let parsed: Option<(String, f32)> =
+                entry.split_once('=').and_then(|(model, temperature)| {
+                    Some((
+                        normalize_model(model.trim()),
+                        temperature.trim().parse().ok()?,
+                    ))
+                });
+            if parsed.is_none() {
+                warn!(entry, "ignoring malformed OPEN_AI_MODEL_TEMPERATURES entry");
+            }
+            parsed
+        })
+        .collect()
+}
+
+// Log the backend's system fingerprint and warn when it differs from the one last seen
+// for an identical request, which means the same prompt may now produce different output
+fn track_system_fingerprint(
+    chat_completion: &ChatCompletion,
+    request_fingerprint: &str,
+    system_fingerprint: &str,
+) {
+    debug!(
+        model = %chat_completion.model,
+        system_fingerprint,
+        "received system fingerprint"
+    );
+
+    let mut seen = SYSTEM_FINGERPRINTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    // Forget old requests rather than grow without bound
+    if seen.len() >= MAX_TRACKED_FINGERPRINTS {
+        seen.clear();
+    }
+
+    let previous: Option<String> = seen.insert(
+        request_fingerprint.to_string(),
+        system_fingerprint.to_string(),
+    );
+    if let Some(previous) = previous.filter(|previous| previous != system_fingerprint) {
+        warn!(
+            model = %chat_completion.model,
+            previous_fingerprint = %previous,
+            system_fingerprint,
+            "system fingerprint changed for an identical request"
+        );
+    }
+}
+
+// Decode the SSE body and forward content deltas until the stream ends, fails, or the
+// receiver is dropped, returning the usage reported by a completed stream
+async fn forward_stream(
+    mut response: Response,
+    tx: mpsc::Sender<Result<String, CallGptError>>,
+    max_frame_bytes: usize,
+    idle_timeout: Option<Duration>,
+) -> Option<APIUsage> {
+    let mut decoder: DeltaDecoder = DeltaDecoder::default();
+
+    while !decoder.is_done() {
+        let chunk = match idle_timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, response.chunk()).await {
+                Ok(chunk) => chunk,
+                Err(_) => {
+                    warn!(?timeout, "stream went silent, aborting");
+                    tx.send(Err(CallGptError::StreamIdle { timeout }))
+                        .await
+                        .ok();
+                    return None;
+                }
+            },
+            None => response.chunk().await,
+        };
+        let bytes = match chunk {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => break,
+            Err(e) => {
+                tx.send(Err(e.into())).await.ok();
+                return None;
+            }
+        };
+
+        for content in decoder.push(&bytes) {
+            if tx.send(Ok(content)).await.is_err() {
+                return None;
+            }
+        }
+
+        // An event that never terminates would otherwise be buffered without bound
+        if decoder.buffered_len() > max_frame_bytes {
+            let limit: usize = max_frame_bytes;
+            tx.send(Err(CallGptError::ResponseTooLarge { limit }))
+                .await
+                .ok();
+            return None;
+        }
+    }
+
+    match decoder.finish() {
+        Ok(deltas) => {
+            for content in deltas {
+                if tx.send(Ok(content)).await.is_err() {
+                    return None;
+                }
+            }
+        }
+        Err(e) => {
+            tx.send(Err(e)).await.ok();
+        }
+    }
+    decoder.usage()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::test_support::{message, test_client, test_config, user_messages};
+    use crate::models::general::llm::ToolCall;
+    use wiremock::matchers::{body_partial_json, body_string_contains, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn tests_describe_request() {
+        let client: GptClient =
+            GptClient::new(GptConfig::new("sk-secret0123456789abcdef", "org-config")).unwrap();
+        let request: Request = client
+            .http
+            .post(client.url("chat/completions"))
+            .headers(client.headers(&CallParams::default()).unwrap())
+            .json(&client.chat_completion(Vec::new(), &CallParams::default()))
+            .build()
+            .unwrap();
+
+        let description: String = describe_request(&request);
+
+        assert!(description.starts_with("POST https://api.openai.com/v1/chat/completions\n"));
+        assert!(description.contains("authorization: Bearer ***\n"));
+        assert!(!description.contains("sk-secret"));
+        assert!(description.ends_with(r#"{"model":"gpt-4o","messages":[],"temperature":0.1}"#));
+    }
+
+    #[test]
+    fn tests_region() {
+        assert_eq!(
+            "EU".parse::<Region>().unwrap().base_url(),
+            "https://eu.api.openai.com/v1"
+        );
+        assert_eq!("us".parse::<Region>().unwrap().base_url(), OPENAI_BASE_URL);
+        assert!("mars".parse::<Region>().is_err());
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_complete() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(header("authorization", "Bearer sk-config"))
+            .and(header("user-agent", "my-app/2.0"))
+            .and(header("openai-beta", "assistants=v2"))
+            .and(body_partial_json(serde_json::json!({ "model": "gpt-4o-mini" })))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{ "choices": [{ "message": { "role": "assistant", "content": "Configured" } }] }"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = GptClient::new(GptConfig {
+            model: "gpt-4o-mini".to_string(),
+            timeout: Some(Duration::from_secs(5)),
+            user_agent: "my-app/2.0".to_string(),
+            beta: Some("assistants=v2".to_string()),
+            ..test_config(&server)
+        })
+        .unwrap();
+
+        let res: String = client.complete(user_messages()).await.unwrap();
+
+        assert_eq!(res, "Configured");
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_extra_headers() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(header("x-cost-center", "research"))
+            .and(header("authorization", "Bearer sk-config"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{ "choices": [{ "message": { "role": "assistant", "content": "Routed" } }] }"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+        let messages: Vec<Message> = user_messages();
+
+        let mut params: CallParams = CallParams::default();
+        params
+            .extra_headers
+            .insert("X-Cost-Center".to_string(), "research".to_string());
+        let res: String = client
+            .complete_with(messages.clone(), &params)
+            .await
+            .unwrap();
+        assert_eq!(res, "Routed");
+
+        params
+            .extra_headers
+            .insert("Authorization".to_string(), "Bearer sk-stolen".to_string());
+        let err: CallGptError = client.complete_with(messages, &params).await.unwrap_err();
+        assert!(matches!(err, CallGptError::ForbiddenHeader(_)));
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_fallback_model() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({ "model": "gpt-4o" })))
+            .respond_with(ResponseTemplate::new(503).set_body_string("overloaded"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({ "model": "gpt-4o-mini" }),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{ "choices": [{ "message": { "role": "assistant", "content": "Fallback" } }], "system_fingerprint": "fp_44709d6fcb" }"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+        let params: CallParams = CallParams {
+            fallback_model: Some("gpt-4o-mini".to_string()),
+            temperature: Some(0.7),
+            ..CallParams::default()
+        };
+
+        let res: CompletionResult = client
+            .complete_detailed(user_messages(), &params)
+            .await
+            .unwrap();
+
+        assert_eq!(res.content, "Fallback");
+        assert_eq!(res.model, "gpt-4o-mini");
+        assert_eq!(res.temperature, 0.7);
+        assert_eq!(res.system_fingerprint.as_deref(), Some("fp_44709d6fcb"));
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_prefill() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [
+                    { "role": "user", "content": "List three colors as JSON" },
+                    { "role": "assistant", "content": "[\"" }
+                ]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{ "choices": [{ "message": { "role": "assistant", "content": "red\", \"green\", \"blue\"]" } }] }"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+        let params: CallParams = CallParams {
+            prefill: Some("[\"".to_string()),
+            ..CallParams::default()
+        };
+
+        let res: String = client
+            .complete_with(vec![message("user", "List three colors as JSON")], &params)
+            .await
+            .unwrap();
+
+        assert_eq!(res, r#"["red", "green", "blue"]"#);
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_verify_credentials() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/models"))
+            .and(header("authorization", "Bearer sk-good"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{ "data": [] }"#))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/models"))
+            .and(header("authorization", "Bearer sk-bad"))
+            .respond_with(ResponseTemplate::new(401).set_body_string(
+                r#"{ "error": { "message": "Incorrect API key provided", "code": "invalid_api_key" } }"#,
+            ))
+            .mount(&server)
+            .await;
+
+        let good: GptClient = GptClient::new(GptConfig {
+            base_url: server.uri(),
+            ..GptConfig::new("sk-good", "org-config")
+        })
+        .unwrap();
+        let bad: GptClient = GptClient::new(GptConfig {
+            base_url: server.uri(),
+            ..GptConfig::new("sk-bad", "org-config")
+        })
+        .unwrap();
+
+        assert!(good.verify_credentials().await.is_ok());
+        assert!(matches!(
+            bad.verify_credentials().await,
+            Err(CallGptError::Api { status: 401, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_list_models() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/models"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{ "object": "list", "data": [
+                    { "id": "gpt-4o", "object": "model", "created": 1715367049, "owned_by": "system" },
+                    { "id": "gpt-4o-mini", "object": "model", "created": 1721172741, "owned_by": "system" }
+                ] }"#,
+            ))
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+
+        assert_eq!(
+            client.list_models().await.unwrap(),
+            vec!["gpt-4o".to_string(), "gpt-4o-mini".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_moderate() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/moderations"))
+            .and(body_partial_json(
+                serde_json::json!({ "input": "some text" }),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{ "id": "modr-1", "model": "omni-moderation-latest", "results": [{
+                    "flagged": true,
+                    "categories": { "violence": true, "harassment": false },
+                    "category_scores": { "violence": 0.91, "harassment": 0.02 }
+                }] }"#,
+            ))
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+
+        let res: ModerationResult = client.moderate("some text").await.unwrap();
+
+        assert!(res.flagged);
+        assert_eq!(res.categories.get("violence"), Some(&true));
+        assert_eq!(res.category_scores.get("violence"), Some(&0.91));
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_moderate_empty() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/moderations"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{ "id": "modr-1", "model": "omni-moderation-latest", "results": [] }"#,
+            ))
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+
+        let err: CallGptError = client.moderate("some text").await.unwrap_err();
+
+        assert!(matches!(err, CallGptError::EmptyModeration));
+        assert_eq!(
+            err.to_string(),
+            "OpenAI moderation response contained no results"
+        );
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_complete_stream() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({ "stream": true })))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "text/event-stream")
+                    .set_body_string(concat!(
+                        "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n",
+                        "data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n",
+                        "data: {\"choices\":[{\"delta\":{\"content\":\"lo\"}}]}\n\n",
+                        "data: [DONE]\n\n"
+                    )),
+            )
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+
+        let mut rx = client
+            .complete_stream(user_messages(), &CallParams::default())
+            .await
+            .unwrap();
+
+        let mut chunks: Vec<String> = Vec::new();
+        while let Some(chunk) = rx.recv().await {
+            chunks.push(chunk.unwrap());
+        }
+        assert_eq!(chunks, vec!["Hel".to_string(), "lo".to_string()]);
+
+        let mut tokens: Vec<String> = Vec::new();
+        let content: String = client
+            .complete_stream_with(user_messages(), &CallParams::default(), |token| {
+                tokens.push(token.to_string())
+            })
+            .await
+            .unwrap();
+        assert_eq!(content, "Hello");
+        assert_eq!(tokens, chunks);
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_complete_choices() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [
+                    { "index": 0, "message": { "role": "assistant", "content": "A" }, "finish_reason": "stop" },
+                    { "index": 1, "message": { "role": "assistant", "content": "B" }, "finish_reason": "length" }
+                ]
+            })))
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+
+        let choices: Vec<APIChoice> = client
+            .complete_choices(user_messages(), &CallParams::default())
+            .await
+            .unwrap();
+
+        assert_eq!(choices.len(), 2);
+        assert_eq!(choices[1].index, 1);
+        assert_eq!(choices[1].message.role.as_deref(), Some("assistant"));
+        assert_eq!(choices[1].message.content.as_deref(), Some("B"));
+        assert_eq!(choices[1].finish_reason.as_deref(), Some("length"));
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_tool_call_exchange() {
+        let server: MockServer = MockServer::start().await;
+        let tool_call: serde_json::Value = serde_json::json!({
+            "id": "call_abc123",
+            "type": "function",
+            "function": { "name": "get_weather", "arguments": "{\"city\":\"Paris\"}" }
+        });
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{
+                    "message": { "role": "assistant", "content": null, "tool_calls": [tool_call] },
+                    "finish_reason": "tool_calls"
+                }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [
+                    { "role": "user", "content": "Hello" },
+                    { "role": "assistant", "tool_calls": [tool_call] },
+                    { "role": "tool", "tool_call_id": "call_abc123", "content": "{\"temperature\":21}" }
+                ]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": "It is 21 degrees." } }]
+            })))
+            .expect(1)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+        let mut messages: Vec<Message> = user_messages();
+
+        let choices: Vec<APIChoice> = client
+            .complete_choices(messages.clone(), &CallParams::default())
+            .await
+            .unwrap();
+        let tool_calls: Vec<ToolCall> = choices[0].message.tool_calls.clone().unwrap();
+        assert_eq!(tool_calls[0].function.name, "get_weather");
+        assert_eq!(tool_calls[0].function.arguments, r#"{"city":"Paris"}"#);
+
+        messages.push(Message {
+            tool_calls: Some(tool_calls.clone()),
+            ..message("assistant", "")
+        });
+        messages.push(Message {
+            tool_call_id: Some(tool_calls[0].id.clone()),
+            ..message("tool", r#"{"temperature":21}"#)
+        });
+        let reply: String = client.complete(messages).await.unwrap();
+
+        assert_eq!(reply, "It is 21 degrees.");
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_embeddings_chunked() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/embeddings"))
+            .and(body_partial_json(serde_json::json!({
+                "model": "text-embedding-3-small",
+                "input": ["abcdefghijklmnopqrstuvwxyzabcdefghijklmn", "klmnopqrst"]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": [
+                    { "index": 1, "embedding": [0.0, 1.0] },
+                    { "index": 0, "embedding": [1.0, 0.0] }
+                ]
+            })))
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+
+        let chunks: Vec<EmbeddedChunk> = client
+            .embeddings_chunked(
+                "abcdefghijklmnopqrstuvwxyzabcdefghijklmnopqrst",
+                "text-embedding-3-small",
+                10,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].range, 0..40);
+        assert_eq!(chunks[0].embedding, vec![1.0, 0.0]);
+        assert_eq!(chunks[1].range, 36..46);
+        assert_eq!(chunks[1].embedding, vec![0.0, 1.0]);
+        assert!(matches!(
+            client
+                .embeddings_chunked("abc", "text-embedding-3-small", 0)
+                .await,
+            Err(CallGptError::InvalidChunkSize(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_max_response_bytes() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/models"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("x".repeat(2048)))
+            .mount(&server)
+            .await;
+
+        let client: GptClient = GptClient::new(GptConfig {
+            max_response_bytes: 1024,
+            ..test_config(&server)
+        })
+        .unwrap();
+
+        assert!(matches!(
+            client.verify_credentials().await,
+            Err(CallGptError::ResponseTooLarge { limit: 1024 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_model_temperatures() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{ "choices": [{ "message": { "role": "assistant", "content": "Hi" } }] }"#,
+            ))
+            .mount(&server)
+            .await;
+
+        let client: GptClient = GptClient::new(GptConfig {
+            model_temperatures: parse_model_temperatures("gpt-4o=0.7, bogus, o1=1"),
+            ..test_config(&server)
+        })
+        .unwrap();
+        let messages: Vec<Message> = user_messages();
+
+        let configured: CompletionResult = client
+            .complete_detailed(messages.clone(), &CallParams::default())
+            .await
+            .unwrap();
+        let unlisted: CompletionResult = client
+            .complete_detailed(
+                messages.clone(),
+                &CallParams {
+                    model: Some("gpt-4o-mini".to_string()),
+                    ..CallParams::default()
+                },
+            )
+            .await
+            .unwrap();
+        let per_call: CompletionResult = client
+            .complete_detailed(
+                messages,
+                &CallParams {
+                    temperature: Some(0.3),
+                    ..CallParams::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(client.config().model_temperatures.len(), 2);
+        assert_eq!(configured.temperature, 0.7);
+        assert_eq!(unlisted.temperature, DEFAULT_TEMPERATURE);
+        assert_eq!(per_call.temperature, 0.3);
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_retry() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/models"))
+            .respond_with(ResponseTemplate::new(429).set_body_string("slow down"))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/models"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(r#"{ "data": [{ "id": "gpt-4o" }] }"#),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = GptClient::new(GptConfig {
+            retry: RetryPolicy {
+                max_attempts: 3,
+                base_delay: Duration::from_millis(1),
+                ..RetryPolicy::default()
+            },
+            ..test_config(&server)
+        })
+        .unwrap();
+
+        assert_eq!(
+            client.list_models().await.unwrap(),
+            vec!["gpt-4o".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_reasoning_effort() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({ "model": "o3-mini", "reasoning_effort": "high" }),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{ "choices": [{ "message": { "role": "assistant", "content": "Reasoned" } }] }"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+        let params: CallParams = CallParams {
+            model: Some("o3-mini".to_string()),
+            reasoning_effort: Some("high".parse().unwrap()),
+            ..CallParams::default()
+        };
+        let messages: Vec<Message> = vec![message("user", "Prove it")];
+
+        let reply: String = client
+            .complete_with(messages.clone(), &params)
+            .await
+            .unwrap();
+        let chat_model: ChatCompletion = client.chat_completion(
+            messages,
+            &CallParams {
+                model: Some("gpt-4o".to_string()),
+                ..params
+            },
+        );
+
+        assert_eq!(reply, "Reasoned");
+        assert_eq!(chat_model.reasoning_effort, None);
+        assert!("extreme".parse::<ReasoningEffort>().is_err());
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_record_and_replay() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{ "choices": [{ "message": { "role": "assistant", "content": "Recorded" } }] }"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let recording: std::path::PathBuf =
+            std::env::temp_dir().join(format!("rust_autogpt_replay_{}.jsonl", std::process::id()));
+        std::fs::remove_file(&recording).ok();
+        let client = |replay: ReplayMode| -> GptClient {
+            GptClient::new(GptConfig {
+                replay: Some(replay),
+                ..test_config(&server)
+            })
+            .unwrap()
+        };
+        let messages = |content: &str| -> Vec<Message> { vec![message("user", content)] };
+
+        let recorded: String = client(ReplayMode::Record(recording.clone()))
+            .complete(messages("Hello"))
+            .await
+            .unwrap();
+        let replayer: GptClient = client(ReplayMode::Replay(recording.clone()));
+        let replayed: String = replayer.complete(messages("Hello")).await.unwrap();
+        let missed: CallGptError = replayer.complete(messages("Other")).await.unwrap_err();
+        std::fs::remove_file(&recording).unwrap();
+
+        assert_eq!(recorded, "Recorded");
+        assert_eq!(replayed, "Recorded");
+        assert!(matches!(missed, CallGptError::ReplayMiss { .. }));
+    }
+
+    // A replaying client whose server fails the test if it is contacted at all
+    async fn replay_client() -> (MockServer, GptClient) {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let client: GptClient = GptClient::new(GptConfig {
+            replay: Some(ReplayMode::Replay(
+                std::env::temp_dir().join("rust_autogpt_unused_replay.jsonl"),
+            )),
+            ..test_config(&server)
+        })
+        .unwrap();
+        (server, client)
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_replay_rejects_stream() {
+        let (_server, client) = replay_client().await;
+
+        let err: CallGptError = client
+            .complete_stream(user_messages(), &CallParams::default())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, CallGptError::ReplayUnsupported { .. }));
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_replay_rejects_embeddings() {
+        let (_server, client) = replay_client().await;
+
+        let err: CallGptError = client
+            .embeddings(vec!["Hello".to_string()], "text-embedding-3-small")
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            CallGptError::ReplayUnsupported {
+                endpoint: "embeddings"
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_replay_rejects_moderation() {
+        let (_server, client) = replay_client().await;
+
+        let err: CallGptError = client.moderate("Hello").await.unwrap_err();
+
+        assert!(matches!(
+            err,
+            CallGptError::ReplayUnsupported {
+                endpoint: "moderations"
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_replay_rejects_list_models() {
+        let (_server, client) = replay_client().await;
+
+        let err: CallGptError = client.list_models().await.unwrap_err();
+
+        assert!(matches!(
+            err,
+            CallGptError::ReplayUnsupported { endpoint: "models" }
+        ));
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_replay_rejects_verify_credentials() {
+        let (_server, client) = replay_client().await;
+
+        let err: CallGptError = client.verify_credentials().await.unwrap_err();
+
+        assert!(matches!(
+            err,
+            CallGptError::ReplayUnsupported { endpoint: "models" }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "models requests cannot be served in replay mode"
+        );
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_max_history_messages() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains("Summarize the following conversation"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": "They said hello." } }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains("Summary of the earlier conversation"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": "Hello again" } }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = GptClient::new(GptConfig {
+            max_history_messages: 3,
+            ..test_config(&server)
+        })
+        .unwrap();
+        let messages: Vec<Message> = (0..6).map(|_| message("user", "Hello")).collect();
+
+        let reply: String = client.complete(messages).await.unwrap();
+
+        assert_eq!(reply, "Hello again");
+    }
+
+    #[test]
+    fn tests_gpt_client_max_output_tokens() {
+        let client: GptClient = GptClient::new(GptConfig::new("sk-config", "org-config")).unwrap();
+        let request = |model: &str| -> ChatCompletion {
+            client.chat_completion(
+                Vec::new(),
+                &CallParams {
+                    model: Some(model.to_string()),
+                    max_output_tokens: Some(256),
+                    ..CallParams::default()
+                },
+            )
+        };
+
+        let legacy: ChatCompletion = request("gpt-4o");
+        let reasoning: ChatCompletion = request("o1-mini");
+
+        assert_eq!(
+            (legacy.max_tokens, legacy.max_completion_tokens),
+            (Some(256), None)
+        );
+        assert_eq!(
+            (reasoning.max_tokens, reasoning.max_completion_tokens),
+            (None, Some(256))
+        );
+        assert_eq!(
+            serde_json::to_value(&reasoning).unwrap()["max_completion_tokens"],
+            256
+        );
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_gzip() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let mut encoder: GzEncoder<Vec<u8>> = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(br#"{ "data": [{ "id": "gpt-4o" }] }"#)
+            .unwrap();
+        let compressed: Vec<u8> = encoder.finish().unwrap();
+
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/models"))
+            .and(header("accept-encoding", "gzip"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-encoding", "gzip")
+                    .set_body_bytes(compressed),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+
+        assert_eq!(
+            client.list_models().await.unwrap(),
+            vec!["gpt-4o".to_string()]
+        );
+    }
+
+    #[test]
+    fn tests_gpt_client_extra_fields() {
+        let client: GptClient = GptClient::new(GptConfig::new("sk-config", "org-config")).unwrap();
+        let mut extra: serde_json::Map<String, serde_json::Value> = serde_json::Map::new();
+        extra.insert("seed".to_string(), serde_json::json!(42));
+        extra.insert(
+            "logit_bias".to_string(),
+            serde_json::json!({ "50256": -100 }),
+        );
+
+        let plain: ChatCompletion = client.chat_completion(Vec::new(), &CallParams::default());
+        let extended: ChatCompletion = client.chat_completion(
+            Vec::new(),
+            &CallParams {
+                extra: Some(extra),
+                ..CallParams::default()
+            },
+        );
+        let body: serde_json::Value = serde_json::to_value(&extended).unwrap();
+
+        assert!(serde_json::to_value(&plain).unwrap().get("extra").is_none());
+        assert_eq!(body["seed"], 42);
+        assert_eq!(body["logit_bias"]["50256"], -100);
+        assert_eq!(body["model"], "gpt-4o");
+    }
+
+    #[test]
+    fn tests_gpt_client_system_preamble() {
+        let client: GptClient = GptClient::new(GptConfig {
+            system_preamble: Some("Never delete files.".to_string()),
+            ..GptConfig::new("sk-config", "org-config")
+        })
+        .unwrap();
+
+        let bare: ChatCompletion =
+            client.chat_completion(vec![message("user", "Hi")], &CallParams::default());
+        let with_system: ChatCompletion = client.chat_completion(
+            vec![message("system", "Be terse."), message("user", "Hi")],
+            &CallParams::default(),
+        );
+
+        assert_eq!(bare.messages.len(), 2);
+        assert_eq!(bare.messages[0].role, "system");
+        assert_eq!(bare.messages[0].content, "Never delete files.");
+        assert_eq!(with_system.messages.len(), 2);
+        assert_eq!(
+            with_system.messages[0].content,
+            "Never delete files.\n\nBe terse."
+        );
+    }
+
+    #[test]
+    fn tests_gpt_client_datetime_context() {
+        let client: GptClient = GptClient::new(GptConfig {
+            system_preamble: Some("Never delete files.".to_string()),
+            datetime_format: Some("%Y".to_string()),
+            ..GptConfig::new("sk-config", "org-config")
+        })
+        .unwrap();
+        let default: GptClient = GptClient::new(GptConfig::new("sk-config", "org-config")).unwrap();
+        let year: String = Utc::now().format("%Y").to_string();
+
+        let stamped: ChatCompletion = client.chat_completion(Vec::new(), &CallParams::default());
+        let plain: ChatCompletion = default.chat_completion(Vec::new(), &CallParams::default());
+
+        assert_eq!(stamped.messages.len(), 1);
+        assert_eq!(
+            stamped.messages[0].content,
+            format!(
+                "Never delete files.\n\nCurrent date and time (UTC): {}",
+                year
+            )
+        );
+        assert!(plain.messages.is_empty());
+        assert_eq!(current_datetime("%Q"), None);
+    }
+
+    #[test]
+    fn tests_gpt_client_normalizes_model() {
+        let client: GptClient = GptClient::new(GptConfig {
+            model: "gpt4o".to_string(),
+            ..GptConfig::new("sk-config", "org-config")
+        })
+        .unwrap();
+
+        let configured: ChatCompletion = client.chat_completion(Vec::new(), &CallParams::default());
+        let overridden: ChatCompletion = client.chat_completion(
+            Vec::new(),
+            &CallParams {
+                model: Some("4o mini".to_string()),
+                ..CallParams::default()
+            },
+        );
+
+        assert_eq!(configured.model, "gpt-4o");
+        assert_eq!(overridden.model, "gpt-4o-mini");
+    }
+
+    #[test]
+    fn tests_gpt_client_seed() {
+        let client: GptClient = GptClient::new(GptConfig {
+            seed: Some(7),
+            ..GptConfig::new("sk-config", "org-config")
+        })
+        .unwrap();
+
+        let configured: ChatCompletion = client.chat_completion(Vec::new(), &CallParams::default());
+        let overridden: ChatCompletion = client.chat_completion(
+            Vec::new(),
+            &CallParams {
+                seed: Some(42),
+                ..CallParams::default()
+            },
+        );
+
+        assert_eq!(serde_json::to_value(&configured).unwrap()["seed"], 7);
+        assert_eq!(overridden.seed, Some(42));
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_stream_usage() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "stream": true,
+                "stream_options": { "include_usage": true }
+            })))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "text/event-stream")
+                    .set_body_string(concat!(
+                        "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}],\"usage\":null}\n\n",
+                        "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":8,\"completion_tokens\":1,\"total_tokens\":9}}\n\n",
+                        "data: [DONE]\n\n"
+                    )),
+            )
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+
+        let streamed: StreamedCompletion = client
+            .complete_stream_detailed(
+                Vec::new(),
+                &CallParams {
+                    include_usage: true,
+                    ..CallParams::default()
+                },
+                |_| {},
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(streamed.content, "Hi");
+        assert_eq!(
+            streamed.usage,
+            Some(APIUsage {
+                prompt_tokens: 8,
+                completion_tokens: 1,
+                total_tokens: 9,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_accept_invalid_certs() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpListener;
+        use tokio_native_tls::native_tls::{Identity, TlsAcceptor};
+
+        // Serve an empty model list over TLS with a self-signed certificate
+        let identity: Identity = Identity::from_pkcs8(
+            include_bytes!("testdata/self_signed.crt"),
+            include_bytes!("testdata/self_signed.key"),
+        )
+        .unwrap();
+        let acceptor: tokio_native_tls::TlsAcceptor = TlsAcceptor::new(identity).unwrap().into();
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url: String = format!(
+            "https://localhost:{}",
+            listener.local_addr().unwrap().port()
+        );
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let acceptor: tokio_native_tls::TlsAcceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    // A client that rejects the certificate aborts the handshake
+                    let Ok(mut tls) = acceptor.accept(socket).await else {
+                        return;
+                    };
+                    let mut request: Vec<u8> = vec![0; 8192];
+                    let _read: usize = tls.read(&mut request).await.unwrap();
+                    let body: &str = r#"{ "data": [] }"#;
+                    let response: String = format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    tls.write_all(response.as_bytes()).await.unwrap();
+                    tls.shutdown().await.unwrap();
+                });
+            }
+        });
+
+        let config: GptConfig = GptConfig {
+            base_url,
+            ..GptConfig::new("sk-config", "org-config")
+        };
+        assert!(!config.danger_accept_invalid_certs);
+        let strict: GptClient = GptClient::new(config.clone()).unwrap();
+        let lenient: GptClient = GptClient::new(GptConfig {
+            danger_accept_invalid_certs: true,
+            ..config
+        })
+        .unwrap();
+
+        assert!(matches!(
+            strict.verify_credentials().await,
+            Err(CallGptError::Http(_))
+        ));
+        assert!(lenient.verify_credentials().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_complete_raw() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [
+                    { "index": 0, "message": { "role": "assistant", "content": "Hi" }, "finish_reason": "stop" }
+                ],
+                "system_fingerprint": "fp_raw"
+            })))
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+
+        let (content, res): (String, APIResponse) = client
+            .complete_raw(Vec::new(), &CallParams::default())
+            .await
+            .unwrap();
+
+        assert_eq!(content, "Hi");
+        assert_eq!(res.system_fingerprint.as_deref(), Some("fp_raw"));
+        assert_eq!(res.choices[0].finish_reason.as_deref(), Some("stop"));
+    }
+
+    #[test]
+    fn tests_gpt_client_with_defaults() {
+        let client: GptClient = GptClient::new(GptConfig::new("sk-config", "org-config"))
+            .unwrap()
+            .with_model("gpt-4o-mini")
+            .with_temperature(0.7);
+
+        let defaulted: ChatCompletion = client.chat_completion(Vec::new(), &CallParams::default());
+        let overridden: ChatCompletion = client.chat_completion(
+            Vec::new(),
+            &CallParams {
+                model: Some("gpt-4o".to_string()),
+                temperature: Some(0.2),
+                ..CallParams::default()
+            },
+        );
+
+        assert_eq!(
+            (defaulted.model.as_str(), defaulted.temperature),
+            ("gpt-4o-mini", 0.7)
+        );
+        assert_eq!(
+            (overridden.model.as_str(), overridden.temperature),
+            ("gpt-4o", 0.2)
+        );
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_per_call_tenant() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(header("OpenAI-Organization", "org-tenant"))
+            .and(header("OpenAI-Project", "proj-tenant"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": "Tenant" } }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = GptClient::new(GptConfig {
+            api_project: Some("proj-config".to_string()),
+            ..test_config(&server)
+        })
+        .unwrap();
+
+        let content: String = client
+            .complete_with(
+                Vec::new(),
+                &CallParams {
+                    organization: Some("org-tenant".to_string()),
+                    project: Some("proj-tenant".to_string()),
+                    ..CallParams::default()
+                },
+            )
+            .await
+            .unwrap();
+        let malformed: CallGptError = client
+            .complete_with(
+                Vec::new(),
+                &CallParams {
+                    organization: Some("org\nInjected: yes".to_string()),
+                    ..CallParams::default()
+                },
+            )
+            .await
+            .unwrap_err();
+
+        assert_eq!(content, "Tenant");
+        assert!(matches!(malformed, CallGptError::InvalidHeader(_)));
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_min_length() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": "Yes." } }]
+            })))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains(ELABORATE_INSTRUCTION))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": "Yes, because the borrow checker forbids it." } }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+
+        let content: String = client
+            .complete_with(
+                Vec::new(),
+                &CallParams {
+                    min_length: Some(MinLength {
+                        chars: 20,
+                        retries: 3,
+                    }),
+                    ..CallParams::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(content, "Yes, because the borrow checker forbids it.");
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_min_length_with_prefill() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": "Yes." } }]
+            })))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        // The re-prompt must end with the prefill for the model to continue
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains(ELABORATE_INSTRUCTION))
+            .and(body_string_contains(
+                r#"{"role":"assistant","content":"Answer: "}]"#,
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": "Yes, it does." } }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+
+        let content: String = client
+            .complete_with(
+                Vec::new(),
+                &CallParams {
+                    prefill: Some("Answer: ".to_string()),
+                    min_length: Some(MinLength {
+                        chars: 30,
+                        retries: 1,
+                    }),
+                    ..CallParams::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(content, "Answer: Yes, it does.");
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_stream_idle_timeout() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpListener;
+
+        // Send the headers and one frame, then hold the connection open in silence
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url: String = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            // The request itself is irrelevant, so one read is enough
+            let mut request: Vec<u8> = vec![0; 8192];
+            let _read: usize = socket.read(&mut request).await.unwrap();
+            let frame: &str = "data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n";
+            socket
+                .write_all(
+                    format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\ntransfer-encoding: chunked\r\n\r\n{:x}\r\n{}\r\n",
+                        frame.len(),
+                        frame
+                    )
+                    .as_bytes(),
+                )
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_secs(30)).await;
+        });
+
+        let client: GptClient = GptClient::new(GptConfig {
+            base_url,
+            stream_idle_timeout: Some(Duration::from_millis(100)),
+            ..GptConfig::new("sk-config", "org-config")
+        })
+        .unwrap();
+
+        let mut rx = client
+            .complete_stream(Vec::new(), &CallParams::default())
+            .await
+            .unwrap();
+
+        assert_eq!(rx.recv().await.unwrap().unwrap(), "Hel");
+        assert!(matches!(
+            rx.recv().await.unwrap(),
+            Err(CallGptError::StreamIdle { .. })
+        ));
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_rate_limits() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("x-ratelimit-limit-requests", "500")
+                    .insert_header("x-ratelimit-remaining-requests", "499")
+                    .insert_header("x-ratelimit-remaining-tokens", "29950")
+                    .set_body_json(serde_json::json!({
+                        "choices": [{ "message": { "role": "assistant", "content": "Hi" } }]
+                    })),
+            )
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+        assert_eq!(client.rate_limits(), None);
+
+        let result: CompletionResult = client
+            .complete_detailed(Vec::new(), &CallParams::default())
+            .await
+            .unwrap();
+
+        let expected: RateLimits = RateLimits {
+            limit_requests: Some(500),
+            limit_tokens: None,
+            remaining_requests: Some(499),
+            remaining_tokens: Some(29950),
+        };
+        assert_eq!(result.rate_limits, Some(expected));
+        assert_eq!(client.rate_limits(), Some(expected));
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_rate_limits_per_response() {
+        let server: MockServer = MockServer::start().await;
+        for (prompt, remaining, delay) in [("slow", "10", 100), ("fast", "20", 0)] {
+            Mock::given(method("POST"))
+                .and(path("/chat/completions"))
+                .and(body_string_contains(prompt))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .insert_header("x-ratelimit-remaining-requests", remaining)
+                        .set_body_json(serde_json::json!({
+                            "choices": [{ "message": { "role": "assistant", "content": "Hi" } }]
+                        }))
+                        .set_delay(Duration::from_millis(delay)),
+                )
+                .mount(&server)
+                .await;
+        }
+
+        let client: GptClient = test_client(&server);
+        let params: CallParams = CallParams::default();
+
+        let (slow, fast) = tokio::join!(
+            client.complete_detailed(vec![message("user", "slow")], &params),
+            client.complete_detailed(vec![message("user", "fast")], &params)
+        );
+        let remaining = |result: CompletionResult| -> Option<u64> {
+            result
+                .rate_limits
+                .and_then(|limits| limits.remaining_requests)
+        };
+
+        assert_eq!(remaining(slow.unwrap()), Some(10));
+        assert_eq!(remaining(fast.unwrap()), Some(20));
+        assert_eq!(client.rate_limits().unwrap().remaining_requests, Some(10));
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_single_flight_shares_failure() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(503)
+                    .set_body_string(r#"{ "error": { "message": "Overloaded" } }"#)
+                    .set_delay(Duration::from_millis(100)),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+
+        let (first, second) =
+            tokio::join!(client.complete(Vec::new()), client.complete(Vec::new()));
+        let mut errors: Vec<CallGptError> = vec![first.unwrap_err(), second.unwrap_err()];
+        errors.sort_by_key(|e| matches!(e, CallGptError::SharedFailure { .. }));
+
+        assert!(matches!(errors[0], CallGptError::Api { status: 503, .. }));
+        assert!(matches!(
+            errors[1],
+            CallGptError::SharedFailure {
+                status: Some(503),
+                retryable: true,
+                ..
+            }
+        ));
+        assert!(errors[1].to_string().contains("Overloaded"));
+    }
+
+    #[tokio::test]
+    async fn tests_gpt_client_single_flight_keys_on_headers() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(
+                        r#"{ "choices": [{ "message": { "role": "assistant", "content": "Hi" } }] }"#,
+                    )
+                    .set_delay(Duration::from_millis(100)),
+            )
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+        let tenant = |name: &str| -> CallParams {
+            CallParams {
+                extra_headers: HashMap::from([("X-Tenant".to_string(), name.to_string())]),
+                ..CallParams::default()
+            }
+        };
+        let (acme, globex) = (tenant("acme"), tenant("globex"));
+
+        let (first, second) = tokio::join!(
+            client.complete_with(Vec::new(), &acme),
+            client.complete_with(Vec::new(), &globex)
+        );
+
+        assert_eq!(first.unwrap(), "Hi");
+        assert_eq!(second.unwrap(), "Hi");
+    }
+}
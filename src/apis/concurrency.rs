@@ -0,0 +1,97 @@
+//! Limits on the number of requests in flight to the API.
+//!
+//! Every request sent by a [`GptClient`](crate::apis::client::GptClient) holds a permit
+//! while it runs. A client built with a
+//! [`concurrency_limit`](crate::apis::client::GptConfig::concurrency_limit) takes its
+//! permits from that limit; every other client shares one process-wide semaphore, which
+//! has no limit until [`set_max_concurrent_requests`] is called, typically once at
+//! startup.
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+static REQUEST_LIMIT: Mutex<Option<ConcurrencyLimit>> = Mutex::new(None);
+
+/// A cap on concurrent requests that can be shared by several clients, e.g. those
+/// talking to one rate-limited gateway. Clones share the same permits.
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimit {
+    semaphore: Arc<Semaphore>,
+}
+
+impl ConcurrencyLimit {
+    /// Creates a limit allowing `permits` requests in flight at once.
+    pub fn new(permits: NonZeroUsize) -> Self {
+        ConcurrencyLimit {
+            semaphore: Arc::new(Semaphore::new(permits.get())),
+        }
+    }
+
+    // The semaphore is never closed, so acquiring cannot fail
+    async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        self.semaphore.clone().acquire_owned().await.ok()
+    }
+}
+
+/// Caps the number of requests in flight across the whole process at `permits`, for
+/// every client without a limit of its own.
+///
+/// Requests already running keep the permit they hold; only requests started after the
+/// call are counted against the new limit. Passing `None` removes the limit.
+pub fn set_max_concurrent_requests(permits: Option<NonZeroUsize>) {
+    *REQUEST_LIMIT.lock().unwrap_or_else(|e| e.into_inner()) = permits.map(ConcurrencyLimit::new);
+}
+
+/// Waits for a permit under `limit`, else under the process-wide limit, or returns
+/// `None` immediately if neither is set.
+pub(crate) async fn acquire_request_permit(
+    limit: Option<&ConcurrencyLimit>,
+) -> Option<OwnedSemaphorePermit> {
+    let limit: Option<ConcurrencyLimit> = match limit {
+        Some(limit) => Some(limit.clone()),
+        None => REQUEST_LIMIT
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone(),
+    };
+
+    match limit {
+        Some(limit) => limit.acquire().await,
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn tests_concurrency_limit() {
+        let limit: ConcurrencyLimit = ConcurrencyLimit::new(NonZeroUsize::MIN);
+
+        let held: Option<OwnedSemaphorePermit> = acquire_request_permit(Some(&limit)).await;
+        let blocked = tokio::time::timeout(
+            Duration::from_millis(50),
+            acquire_request_permit(Some(&limit)),
+        )
+        .await;
+        drop(held);
+
+        assert!(blocked.is_err());
+        assert!(acquire_request_permit(Some(&limit)).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn tests_set_max_concurrent_requests() {
+        // Large enough that tests running in parallel are never held up by it
+        let permits: NonZeroUsize = NonZeroUsize::new(10_000).unwrap();
+
+        set_max_concurrent_requests(Some(permits));
+        let permit: Option<OwnedSemaphorePermit> = acquire_request_permit(None).await;
+        set_max_concurrent_requests(None);
+
+        assert!(permit.is_some());
+        assert!(acquire_request_permit(None).await.is_none());
+    }
+}
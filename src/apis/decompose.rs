@@ -0,0 +1,96 @@
+//! Decomposition of a large project description into smaller sub-tasks.
+use crate::apis::client::GptClient;
+use crate::apis::error::CallGptError;
+use crate::apis::params::CallParams;
+use crate::apis::structured::{complete_json, DEFAULT_JSON_ATTEMPTS};
+use crate::models::general::llm::{Message, SubTask};
+
+const DECOMPOSE_INSTRUCTION: &str = "Split the project described by the user into \
+independent sub-tasks, each small enough to plan and implement on its own without the \
+others in view. Reply with only a JSON array of objects with string fields `title` and \
+`description`, where the description restates every requirement the sub-task needs.";
+
+/// Asks the model to break `description` into independent sub-tasks that can each be
+/// processed in a separate, context-friendly call.
+///
+/// A reply that is not a JSON array of sub-tasks is re-prompted up to
+/// [`DEFAULT_JSON_ATTEMPTS`] times through [`complete_json`], which shares its retry loop
+/// with the other validated completions.
+///
+/// # Errors
+///
+/// Returns [`CallGptError::SchemaMismatch`] or [`CallGptError::Deserialize`] when no
+/// attempt produced valid sub-tasks, or any error from the underlying calls.
+pub async fn decompose_task(
+    client: &GptClient,
+    description: &str,
+) -> Result<Vec<SubTask>, CallGptError> {
+    let messages: Vec<Message> = vec![
+        Message::new("system", DECOMPOSE_INSTRUCTION),
+        Message::new("user", description),
+    ];
+    complete_json(
+        client,
+        messages,
+        &CallParams::default(),
+        DEFAULT_JSON_ATTEMPTS,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::test_support::{reply, test_client};
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer};
+
+    #[tokio::test]
+    async fn tests_decompose_task_reprompts_invalid_json() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(reply("Sure! First build the API, then the UI."))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains("could not be used"))
+            .respond_with(reply(
+                r#"[{ "title": "API", "description": "Build the REST API." }, { "title": "UI", "description": "Build the web UI." }]"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+
+        let subtasks: Vec<SubTask> =
+            decompose_task(&client, "A todo app with a REST API and a web UI")
+                .await
+                .unwrap();
+
+        assert_eq!(subtasks.len(), 2);
+        assert_eq!(subtasks[0].title, "API");
+        assert_eq!(subtasks[1].description, "Build the web UI.");
+    }
+
+    #[tokio::test]
+    async fn tests_decompose_task_gives_up() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(reply("I would rather not."))
+            .expect(u64::from(DEFAULT_JSON_ATTEMPTS))
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+
+        let err: CallGptError = decompose_task(&client, "Anything").await.unwrap_err();
+
+        assert!(matches!(err, CallGptError::Deserialize { .. }));
+    }
+}
@@ -0,0 +1,362 @@
+use crate::helpers::manifest::ManifestError;
+use crate::helpers::redact::redact;
+use crate::helpers::tokens::ChunkSizeError;
+use crate::models::general::llm::APIErrorResponse;
+use std::fmt;
+
+// Longest slice of a response body kept in an error message
+const MAX_BODY_CHARS: usize = 1_000;
+
+/// Errors returned by the OpenAI API helpers.
+#[derive(Debug)]
+pub enum CallGptError {
+    /// A required environment variable is not set.
+    MissingEnv(&'static str),
+    /// An environment variable is set to a value outside the accepted set.
+    InvalidEnv {
+        name: &'static str,
+        value: String,
+        expected: String,
+    },
+    /// A header value (API key, organization or extra header) contains invalid characters.
+    InvalidHeader(reqwest::header::InvalidHeaderValue),
+    /// An extra header name is not a valid HTTP header name.
+    InvalidHeaderName(reqwest::header::InvalidHeaderName),
+    /// An extra header tried to override a header managed by the client.
+    ForbiddenHeader(String),
+    /// The HTTP client could not be built or the request failed.
+    Http(reqwest::Error),
+    /// The API answered with a non-success status. `message` and `code` come from the
+    /// error envelope when present, otherwise `message` is the truncated body.
+    Api {
+        status: u16,
+        code: Option<String>,
+        message: String,
+    },
+    /// The conversation does not fit the model's context window. Trim the history and
+    /// retry with at most `max_tokens` tokens.
+    ContextLengthExceeded {
+        max_tokens: u32,
+        requested_tokens: u32,
+    },
+    /// The API answered successfully but returned no choices.
+    EmptyChoices,
+    /// The moderation endpoint answered successfully but returned no results.
+    EmptyModeration,
+    /// The response body was larger than the configured `max_response_bytes`.
+    ResponseTooLarge { limit: usize },
+    /// A streamed completion received nothing for longer than the configured
+    /// `stream_idle_timeout`, though the connection stayed open.
+    StreamIdle { timeout: std::time::Duration },
+    /// Replay mode found no recorded response for the request with this fingerprint.
+    ReplayMiss { fingerprint: String },
+    /// Replay mode only serves recorded chat completions, so a request to this endpoint
+    /// could not be answered without the network.
+    ReplayUnsupported { endpoint: &'static str },
+    /// Reading or writing a local file failed.
+    Io(std::io::Error),
+    /// The response body could not be deserialized. `body` holds a truncated copy
+    /// of what the API actually sent back.
+    Deserialize {
+        source: serde_json::Error,
+        body: String,
+    },
+    /// A reply held JSON, but not of the shape the caller asked for. `details` names the
+    /// offending field and `body` holds a truncated copy of the reply.
+    SchemaMismatch { details: String, body: String },
+    /// Text could not be split into chunks of the requested size.
+    InvalidChunkSize(ChunkSizeError),
+    /// A generated `Cargo.toml` was still invalid after every re-prompt.
+    InvalidManifest(ManifestError),
+    /// An identical request this call was waiting on failed (see [`GptClient`]'s
+    /// single-flight sharing). `status` and `message` summarize that request's error.
+    ///
+    /// [`GptClient`]: crate::apis::client::GptClient
+    SharedFailure {
+        status: Option<u16>,
+        retryable: bool,
+        message: String,
+    },
+}
+
+impl CallGptError {
+    /// Whether the failure is likely transient: rate limiting, a server error, or a
+    /// timeout or connection failure.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            CallGptError::Api { status, .. } => *status == 429 || *status >= 500,
+            CallGptError::Http(e) => e.is_timeout() || e.is_connect(),
+            CallGptError::SharedFailure { retryable, .. } => *retryable,
+            _ => false,
+        }
+    }
+
+    pub(crate) fn api(status: u16, body: &str) -> Self {
+        match serde_json::from_str::<APIErrorResponse>(body) {
+            Ok(envelope) if envelope.error.code.as_deref() == Some("context_length_exceeded") => {
+                match parse_context_length(&envelope.error.message) {
+                    Some((max_tokens, requested_tokens)) => CallGptError::ContextLengthExceeded {
+                        max_tokens,
+                        requested_tokens,
+                    },
+                    None => CallGptError::Api {
+                        status,
+                        code: envelope.error.code,
+                        message: envelope.error.message,
+                    },
+                }
+            }
+            Ok(envelope) => CallGptError::Api {
+                status,
+                code: envelope.error.code,
+                message: envelope.error.message,
+            },
+            Err(_) => CallGptError::Api {
+                status,
+                code: None,
+                message: truncate_body(body),
+            },
+        }
+    }
+
+    pub(crate) fn deserialize(source: serde_json::Error, body: &str) -> Self {
+        CallGptError::Deserialize {
+            source,
+            body: truncate_body(body),
+        }
+    }
+
+    // A cloneable summary of this error for callers that shared the failed request
+    pub(crate) fn shared(&self) -> Self {
+        let status: Option<u16> = match self {
+            CallGptError::Api { status, .. } => Some(*status),
+            CallGptError::ContextLengthExceeded { .. } => Some(400),
+            CallGptError::SharedFailure { status, .. } => *status,
+            _ => None,
+        };
+        let message: String = match self {
+            CallGptError::SharedFailure { message, .. } => message.clone(),
+            e => e.to_string(),
+        };
+        CallGptError::SharedFailure {
+            status,
+            retryable: self.is_retryable(),
+            message,
+        }
+    }
+
+    pub(crate) fn schema_mismatch(details: String, body: &str) -> Self {
+        CallGptError::SchemaMismatch {
+            details,
+            body: truncate_body(body),
+        }
+    }
+}
+
+impl fmt::Display for CallGptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallGptError::MissingEnv(name) => {
+                write!(f, "{} not found in environment variables", name)
+            }
+            CallGptError::InvalidEnv {
+                name,
+                value,
+                expected,
+            } => write!(
+                f,
+                "{} has unknown value `{}` (expected {})",
+                name, value, expected
+            ),
+            CallGptError::InvalidHeader(e) => write!(f, "invalid header value: {}", e),
+            CallGptError::InvalidHeaderName(e) => write!(f, "invalid header name: {}", e),
+            CallGptError::ForbiddenHeader(name) => {
+                write!(f, "the {} header cannot be overridden", name)
+            }
+            CallGptError::Http(e) => {
+                write!(f, "request to OpenAI failed: {}", redact(&e.to_string()))
+            }
+            CallGptError::Api {
+                status,
+                code: Some(code),
+                message,
+            } => write!(
+                f,
+                "OpenAI returned {} ({}): {}",
+                status,
+                code,
+                redact(message)
+            ),
+            CallGptError::Api {
+                status, message, ..
+            } => write!(f, "OpenAI returned {}: {}", status, redact(message)),
+            CallGptError::ContextLengthExceeded {
+                max_tokens,
+                requested_tokens,
+            } => write!(
+                f,
+                "context length exceeded: {} tokens requested, model maximum is {}",
+                requested_tokens, max_tokens
+            ),
+            CallGptError::EmptyChoices => write!(f, "OpenAI response contained no choices"),
+            CallGptError::EmptyModeration => {
+                write!(f, "OpenAI moderation response contained no results")
+            }
+            CallGptError::ResponseTooLarge { limit } => {
+                write!(f, "OpenAI response body exceeded the {} byte limit", limit)
+            }
+            CallGptError::StreamIdle { timeout } => write!(
+                f,
+                "OpenAI stream sent nothing for {:?} and was aborted",
+                timeout
+            ),
+            CallGptError::ReplayMiss { fingerprint } => write!(
+                f,
+                "no recorded response for request {} in replay mode",
+                fingerprint
+            ),
+            CallGptError::ReplayUnsupported { endpoint } => {
+                write!(f, "{} requests cannot be served in replay mode", endpoint)
+            }
+            CallGptError::Io(e) => write!(f, "I/O error: {}", e),
+            CallGptError::Deserialize { source, body } => write!(
+                f,
+                "failed to parse OpenAI response: {} (response body: {})",
+                source,
+                redact(body)
+            ),
+            CallGptError::SchemaMismatch { details, body } => write!(
+                f,
+                "output didn't match expected schema: {} (output: {})",
+                details,
+                redact(body)
+            ),
+            CallGptError::InvalidChunkSize(e) => write!(f, "invalid chunk size: {}", e),
+            CallGptError::InvalidManifest(e) => {
+                write!(f, "generated manifest failed validation: {}", e)
+            }
+            CallGptError::SharedFailure { message, .. } => {
+                write!(f, "shared in-flight request failed: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CallGptError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CallGptError::MissingEnv(_) | CallGptError::InvalidEnv { .. } => None,
+            CallGptError::Api { .. }
+            | CallGptError::ContextLengthExceeded { .. }
+            | CallGptError::EmptyChoices
+            | CallGptError::EmptyModeration
+            | CallGptError::ResponseTooLarge { .. }
+            | CallGptError::StreamIdle { .. }
+            | CallGptError::SchemaMismatch { .. }
+            | CallGptError::SharedFailure { .. }
+            | CallGptError::ReplayMiss { .. }
+            | CallGptError::ReplayUnsupported { .. } => None,
+            CallGptError::InvalidHeader(e) => Some(e),
+            CallGptError::InvalidHeaderName(e) => Some(e),
+            CallGptError::ForbiddenHeader(_) => None,
+            CallGptError::Http(e) => Some(e),
+            CallGptError::Io(e) => Some(e),
+            CallGptError::Deserialize { source, .. } => Some(source),
+            CallGptError::InvalidChunkSize(e) => Some(e),
+            CallGptError::InvalidManifest(e) => Some(e),
+        }
+    }
+}
+
+impl From<reqwest::header::InvalidHeaderValue> for CallGptError {
+    fn from(e: reqwest::header::InvalidHeaderValue) -> Self {
+        CallGptError::InvalidHeader(e)
+    }
+}
+
+impl From<reqwest::header::InvalidHeaderName> for CallGptError {
+    fn from(e: reqwest::header::InvalidHeaderName) -> Self {
+        CallGptError::InvalidHeaderName(e)
+    }
+}
+
+impl From<std::io::Error> for CallGptError {
+    fn from(e: std::io::Error) -> Self {
+        CallGptError::Io(e)
+    }
+}
+
+impl From<reqwest::Error> for CallGptError {
+    fn from(e: reqwest::Error) -> Self {
+        CallGptError::Http(e)
+    }
+}
+
+// Pull (maximum, requested) token counts out of messages such as "This model's maximum
+// context length is 128000 tokens. However, your messages resulted in 130532 tokens."
+fn parse_context_length(message: &str) -> Option<(u32, u32)> {
+    let max_tokens: u32 = number_after(message, "maximum context length is")?;
+    let requested_tokens: u32 =
+        number_after(message, "resulted in").or_else(|| number_after(message, "you requested"))?;
+    Some((max_tokens, requested_tokens))
+}
+
+fn number_after(message: &str, phrase: &str) -> Option<u32> {
+    let start: usize = message.find(phrase)? + phrase.len();
+    let digits: String = message[start..]
+        .trim_start()
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+// Keep error messages readable when the API returns a large page
+fn truncate_body(body: &str) -> String {
+    match body.char_indices().nth(MAX_BODY_CHARS) {
+        Some((cut, _)) => format!("{}... ({} bytes total)", &body[..cut], body.len()),
+        None => body.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_deserialize_error_truncates_body() {
+        let body: String = "x".repeat(MAX_BODY_CHARS + 500);
+        let source: serde_json::Error = serde_json::from_str::<u8>("nope").unwrap_err();
+
+        let message: String = CallGptError::deserialize(source, &body).to_string();
+
+        assert!(message.contains(&"x".repeat(MAX_BODY_CHARS)));
+        assert!(!message.contains(&"x".repeat(MAX_BODY_CHARS + 1)));
+        assert!(message.contains("1500 bytes total"));
+    }
+
+    #[test]
+    fn tests_context_length_exceeded() {
+        let body: &str = r#"{ "error": {
+            "message": "This model's maximum context length is 128000 tokens. However, your messages resulted in 130532 tokens. Please reduce the length of the messages.",
+            "type": "invalid_request_error",
+            "param": "messages",
+            "code": "context_length_exceeded"
+        } }"#;
+
+        assert!(matches!(
+            CallGptError::api(400, body),
+            CallGptError::ContextLengthExceeded {
+                max_tokens: 128000,
+                requested_tokens: 130532
+            }
+        ));
+
+        let without_numbers: &str =
+            r#"{ "error": { "message": "Too long.", "code": "context_length_exceeded" } }"#;
+        assert!(matches!(
+            CallGptError::api(400, without_numbers),
+            CallGptError::Api { status: 400, .. }
+        ));
+    }
+}
@@ -0,0 +1,312 @@
+//! Keeping a conversation's history within bounds by summarizing its older turns.
+use crate::apis::client::GptClient;
+use crate::apis::error::CallGptError;
+use crate::apis::params::CallParams;
+use crate::models::general::llm::Message;
+use tracing::{info, warn};
+
+/// Number of most recent messages `summarize_history` keeps verbatim.
+pub const DEFAULT_RECENT_TURNS: usize = 4;
+
+/// Default `max_history_messages` of a [`GptConfig`](crate::apis::client::GptConfig),
+/// high enough that ordinary conversations never reach it.
+pub const DEFAULT_MAX_HISTORY_MESSAGES: usize = 1000;
+
+const SUMMARY_INSTRUCTION: &str = "Summarize the following conversation in a few sentences. \
+Keep every decision, requirement, name and number that later turns may depend on. \
+Reply with the summary only.";
+
+/// Compresses a long conversation by replacing its oldest turns with a summary.
+///
+/// Equivalent to [`summarize_history_keeping`] with [`DEFAULT_RECENT_TURNS`].
+pub async fn summarize_history(
+    client: &GptClient,
+    messages: Vec<Message>,
+    model: &str,
+) -> Result<Vec<Message>, CallGptError> {
+    summarize_history_keeping(client, messages, model, DEFAULT_RECENT_TURNS).await
+}
+
+/// Compresses a long conversation by replacing its oldest turns with a summary.
+///
+/// Leading system messages and the last `keep_recent` messages are kept verbatim. Every
+/// turn in between is summarized by `model`, called through `client`, into a single
+/// system message inserted right
+/// after the leading system messages. When there is nothing to summarize the history is
+/// returned unchanged without calling the API.
+///
+/// # Errors
+///
+/// Returns any error from the underlying summarization call.
+pub async fn summarize_history_keeping(
+    client: &GptClient,
+    messages: Vec<Message>,
+    model: &str,
+    keep_recent: usize,
+) -> Result<Vec<Message>, CallGptError> {
+    let (mut summarized, older, recent) = split_history(messages, keep_recent);
+    if older.is_empty() {
+        summarized.extend(recent);
+        return Ok(summarized);
+    }
+
+    // Render the old turns as a plain transcript for the model to summarize
+    let transcript: String = older
+        .iter()
+        .map(|message| format!("{}: {}", message.role, message.content))
+        .collect::<Vec<String>>()
+        .join("\n\n");
+
+    let request: Vec<Message> = vec![
+        Message::new("system", SUMMARY_INSTRUCTION),
+        Message::new("user", transcript),
+    ];
+    let params: CallParams = CallParams {
+        model: Some(model.to_string()),
+        ..CallParams::default()
+    };
+    // Sent whole so the summary request is not itself checked against the history limit
+    let summary: String = client
+        .complete_request(&client.chat_completion(request, &params))
+        .await?;
+
+    summarized.push(Message::new(
+        "system",
+        format!("Summary of the earlier conversation:\n{}", summary.trim()),
+    ));
+    summarized.extend(recent);
+    Ok(summarized)
+}
+
+/// Summarizes the conversation once it holds more than `max_messages` messages, so a long
+/// session cannot grow without bound. Below the cap the history is returned unchanged
+/// without calling the API.
+///
+/// When trimming, the leading system messages and up to [`DEFAULT_RECENT_TURNS`] recent
+/// messages are kept, fewer if needed to fit under the cap, as in
+/// [`summarize_history_keeping`]. The result then holds at most `max_messages` messages,
+/// unless the leading system messages alone leave no room for the summary: they are never
+/// summarized, so the result is those system messages plus the summary, and a warning is
+/// logged.
+///
+/// # Errors
+///
+/// Returns any error from the underlying summarization call.
+pub async fn enforce_history_limit(
+    client: &GptClient,
+    messages: Vec<Message>,
+    model: &str,
+    max_messages: usize,
+) -> Result<Vec<Message>, CallGptError> {
+    if messages.len() <= max_messages {
+        return Ok(messages);
+    }
+
+    let leading_system: usize = messages
+        .iter()
+        .take_while(|message| message.role == "system")
+        .count();
+    // Room left after the system messages and the summary itself
+    let keep_recent: usize =
+        DEFAULT_RECENT_TURNS.min(max_messages.saturating_sub(leading_system + 1));
+    if max_messages <= leading_system {
+        warn!(
+            max_messages,
+            leading_system, "system messages alone reach the history limit; it will be exceeded"
+        );
+    }
+    info!(
+        messages = messages.len(),
+        max_messages, keep_recent, "history exceeds its limit, summarizing older turns"
+    );
+    summarize_history_keeping(client, messages, model, keep_recent).await
+}
+
+/// Merges the leading system messages of a conversation into one, joined by blank lines
+/// in their original order, for providers that accept only a single system message at
+/// the front.
+///
+/// System messages that appear after a user or assistant turn are left in place, with a
+/// warning, since moving them would change what the model sees at that point.
+pub fn merge_system_messages(messages: Vec<Message>) -> Vec<Message> {
+    let (leading, rest, _) = split_history(messages, 0);
+
+    if rest.iter().any(|message| message.role == "system") {
+        warn!("system message found after the conversation started; it was not merged");
+    }
+    if leading.len() < 2 {
+        return leading.into_iter().chain(rest).collect();
+    }
+
+    let merged: Message = Message::new(
+        "system",
+        leading
+            .into_iter()
+            .map(|message| message.content)
+            .collect::<Vec<String>>()
+            .join("\n\n"),
+    );
+    std::iter::once(merged).chain(rest).collect()
+}
+
+// Split into (leading system messages, turns to summarize, recent turns kept verbatim)
+fn split_history(
+    mut messages: Vec<Message>,
+    keep_recent: usize,
+) -> (Vec<Message>, Vec<Message>, Vec<Message>) {
+    let leading_system: usize = messages
+        .iter()
+        .take_while(|message| message.role == "system")
+        .count();
+    let recent_start: usize = messages
+        .len()
+        .saturating_sub(keep_recent)
+        .max(leading_system);
+
+    let recent: Vec<Message> = messages.split_off(recent_start);
+    let older: Vec<Message> = messages.split_off(leading_system);
+    (messages, older, recent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::client::GptConfig;
+    use crate::apis::test_support::{message, test_client};
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn tests_split_history() {
+        let messages: Vec<Message> = vec![
+            message("system", "rules"),
+            message("user", "one"),
+            message("assistant", "two"),
+            message("user", "three"),
+            message("assistant", "four"),
+        ];
+
+        let (system, older, recent) = split_history(messages.clone(), 2);
+        assert_eq!(system.len(), 1);
+        assert_eq!(older.len(), 2);
+        assert_eq!(recent[0].content, "three");
+
+        let (system, older, recent) = split_history(messages, 10);
+        assert_eq!(system.len(), 1);
+        assert!(older.is_empty());
+        assert_eq!(recent.len(), 4);
+    }
+
+    #[test]
+    fn tests_merge_system_messages() {
+        let merged: Vec<Message> = merge_system_messages(vec![
+            message("system", "You are a Rust expert."),
+            message("system", "Reply in JSON."),
+            message("user", "Hi"),
+            message("system", "Late rule"),
+        ]);
+
+        assert_eq!(merged.len(), 3);
+        assert_eq!(
+            merged[0].content,
+            "You are a Rust expert.\n\nReply in JSON."
+        );
+        assert_eq!(merged[1].content, "Hi");
+        assert_eq!(merged[2].content, "Late rule");
+    }
+
+    #[tokio::test]
+    async fn tests_enforce_history_limit_under_cap() {
+        let messages: Vec<Message> = vec![message("user", "one"), message("assistant", "two")];
+        let client: GptClient = GptClient::new(GptConfig::new("sk-config", "org-config")).unwrap();
+
+        // Never reaches the API
+        let bounded: Vec<Message> = enforce_history_limit(&client, messages, "gpt-4o", 2)
+            .await
+            .unwrap();
+
+        assert_eq!(bounded.len(), 2);
+        assert_eq!(bounded[1].content, "two");
+    }
+
+    #[tokio::test]
+    async fn tests_summarize_history_keeping() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains("gpt-4o-mini"))
+            .and(body_string_contains("user: one\\n\\nassistant: two"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": " Counted to two. " } }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+        let messages: Vec<Message> = vec![
+            message("system", "rules"),
+            message("user", "one"),
+            message("assistant", "two"),
+            message("user", "three"),
+            message("assistant", "four"),
+        ];
+
+        let summarized: Vec<Message> =
+            summarize_history_keeping(&client, messages, "gpt-4o-mini", 2)
+                .await
+                .unwrap();
+
+        let contents: Vec<&str> = summarized
+            .iter()
+            .map(|message| message.content.as_str())
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                "rules",
+                "Summary of the earlier conversation:\nCounted to two.",
+                "three",
+                "four"
+            ]
+        );
+        assert_eq!(summarized[1].role, "system");
+    }
+
+    #[tokio::test]
+    async fn tests_enforce_history_limit_over_cap() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": "Earlier turns." } }]
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+        let messages: Vec<Message> = std::iter::once(message("system", "rules"))
+            .chain((1..=9).map(|turn| message("user", &turn.to_string())))
+            .collect();
+
+        let bounded: Vec<Message> = enforce_history_limit(&client, messages.clone(), "gpt-4o", 4)
+            .await
+            .unwrap();
+        // Only the system message fits, so the cap is exceeded by the summary
+        let overflowing: Vec<Message> = enforce_history_limit(&client, messages, "gpt-4o", 1)
+            .await
+            .unwrap();
+
+        let contents = |messages: &[Message]| -> Vec<String> {
+            messages
+                .iter()
+                .map(|message| message.content.clone())
+                .collect()
+        };
+        let summary: &str = "Summary of the earlier conversation:\nEarlier turns.";
+        assert_eq!(bounded.len(), 4);
+        assert_eq!(contents(&bounded), vec!["rules", summary, "8", "9"]);
+        assert_eq!(contents(&overflowing), vec!["rules", summary]);
+    }
+}
@@ -0,0 +1,124 @@
+//! Generation of `Cargo.toml` files that are validated before they are used.
+use crate::apis::client::GptClient;
+use crate::apis::error::CallGptError;
+use crate::apis::params::CallParams;
+use crate::apis::structured::{complete_validated, Rejection};
+use crate::helpers::manifest::validate_cargo_toml;
+use crate::helpers::markdown::extract_code_blocks;
+use crate::models::general::llm::Message;
+
+/// Number of replies `complete_cargo_toml` requests before giving up on an invalid
+/// manifest.
+pub const DEFAULT_MANIFEST_ATTEMPTS: u32 = 3;
+
+const REPROMPT_INSTRUCTION: &str = "That Cargo.toml would not build. \
+Reply again with only the corrected Cargo.toml.";
+
+/// Sends `messages`, which should ask for a `Cargo.toml`, and returns the manifest from
+/// the reply once it passes [`validate_cargo_toml`].
+///
+/// The manifest is the first `toml` code block of the reply, else its first code block,
+/// else the whole reply. When it fails validation, the model is given the
+/// [`ManifestError`](crate::helpers::manifest::ManifestError) text and asked to correct
+/// itself (see [`complete_validated`]).
+///
+/// # Errors
+///
+/// Returns [`CallGptError::InvalidManifest`] with the last validation error once every
+/// attempt has failed. Any error from the underlying calls is returned as-is.
+pub async fn complete_cargo_toml(
+    client: &GptClient,
+    messages: Vec<Message>,
+    params: &CallParams,
+    max_attempts: u32,
+) -> Result<String, CallGptError> {
+    complete_validated(client, messages, params, max_attempts, |reply| {
+        let manifest: String = extract_manifest(reply);
+        match validate_cargo_toml(&manifest) {
+            Ok(()) => Ok(manifest),
+            Err(err) => Err(Rejection {
+                feedback: format!("{} {}", REPROMPT_INSTRUCTION, err),
+                error: CallGptError::InvalidManifest(err),
+            }),
+        }
+    })
+    .await
+}
+
+// Prefer a `toml` block, then any block, then the reply itself
+fn extract_manifest(reply: &str) -> String {
+    let blocks: Vec<(Option<String>, String)> = extract_code_blocks(reply);
+    blocks
+        .iter()
+        .find(|(language, _)| language.as_deref() == Some("toml"))
+        .or_else(|| blocks.first())
+        .map(|(_, body)| body.clone())
+        .unwrap_or_else(|| reply.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::test_support::{reply, test_client};
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer};
+
+    #[tokio::test]
+    async fn tests_complete_cargo_toml_reprompts() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(reply("```toml\n[dependencies]\nserde = \"1.0\"\n```"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains("missing `[package]` section"))
+            .respond_with(reply(
+                "Fixed:\n```toml\n[package]\nname = \"demo\"\n\n[dependencies]\nserde = \"1.0\"\n```",
+            ))
+            .expect(1)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+
+        let manifest: String = complete_cargo_toml(
+            &client,
+            Vec::new(),
+            &CallParams::default(),
+            DEFAULT_MANIFEST_ATTEMPTS,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            manifest,
+            "[package]\nname = \"demo\"\n\n[dependencies]\nserde = \"1.0\""
+        );
+    }
+
+    #[tokio::test]
+    async fn tests_complete_cargo_toml_gives_up() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(reply(
+                "[package]\nname = \"demo\"\n\n[dependencies]\nserde = \"latest\"",
+            ))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+
+        let err: CallGptError = complete_cargo_toml(&client, Vec::new(), &CallParams::default(), 2)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, CallGptError::InvalidManifest(_)));
+        assert!(err.to_string().contains("dependencies.serde"));
+    }
+}
@@ -1 +1,14 @@
 pub mod call_request;
+pub mod client;
+pub mod concurrency;
+pub mod decompose;
+pub mod error;
+pub mod history;
+pub mod manifest;
+pub mod params;
+pub mod replay;
+pub mod single_flight;
+pub mod stream;
+pub mod structured;
+#[cfg(test)]
+pub(crate) mod test_support;
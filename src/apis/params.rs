@@ -0,0 +1,69 @@
+use crate::models::general::llm::ReasoningEffort;
+use std::collections::HashMap;
+
+/// Per-call overrides for a chat completion request. Unset fields fall back to the
+/// client's configuration and the crate defaults.
+#[derive(Debug, Clone, Default)]
+pub struct CallParams {
+    /// Model to use instead of the client's configured model. Common aliases such as
+    /// `4o` are mapped to their canonical ID with
+    /// [`normalize_model`](crate::helpers::model_name::normalize_model).
+    pub model: Option<String>,
+    /// Model to switch to if the primary model still fails with a retryable error (rate
+    /// limiting, server errors or timeouts) once the client's retries are used up.
+    pub fallback_model: Option<String>,
+    /// Sampling temperature to use instead of the client's default
+    /// ([`GptConfig::temperature`](crate::apis::client::GptConfig::temperature)), the
+    /// model's configured default
+    /// ([`GptConfig::model_temperatures`](crate::apis::client::GptConfig::model_temperatures))
+    /// or, failing that, `DEFAULT_TEMPERATURE`.
+    pub temperature: Option<f32>,
+    /// How much effort o-series reasoning models spend before answering. Ignored, and not
+    /// sent, for other models. Parse user input with `"low".parse()`, which rejects
+    /// anything but `low`, `medium` and `high`.
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// Upper bound on tokens generated for the reply. Sent as `max_completion_tokens`
+    /// to models that require it (o-series and `gpt-5` models) and as `max_tokens` to
+    /// older ones.
+    pub max_output_tokens: Option<u32>,
+    /// Seed for best-effort deterministic sampling, overriding
+    /// [`GptConfig::seed`](crate::apis::client::GptConfig::seed).
+    pub seed: Option<u64>,
+    /// Start of the assistant's reply. It is sent as a trailing assistant message for
+    /// the model to continue, and is prepended to the returned content so the result
+    /// reads as one complete reply.
+    pub prefill: Option<String>,
+    /// Floor on the reply length. Shorter replies are re-prompted with a request to
+    /// elaborate, see
+    /// [`GptClient::complete_detailed`](crate::apis::client::GptClient::complete_detailed).
+    /// Off by default, and not applied to streamed completions or to calls returning the
+    /// raw choices.
+    pub min_length: Option<MinLength>,
+    /// Ask a streamed completion to report token usage in its final frame
+    /// (`stream_options.include_usage`). Off by default since older OpenAI-compatible
+    /// servers reject the option. Ignored for non-streamed calls.
+    pub include_usage: bool,
+    /// Organization to bill the call to instead of
+    /// [`GptConfig::api_org`](crate::apis::client::GptConfig::api_org), e.g. when one
+    /// process serves several tenants.
+    pub organization: Option<String>,
+    /// Project to attribute the call to instead of
+    /// [`GptConfig::api_project`](crate::apis::client::GptConfig::api_project).
+    pub project: Option<String>,
+    /// Additional headers merged into the request, e.g. for gateways that route or
+    /// tag requests. The `authorization` header cannot be overridden.
+    pub extra_headers: HashMap<String, String>,
+    /// Raw fields merged into the request body, for provider parameters this crate does
+    /// not model yet. Collisions with the typed fields are the caller's responsibility;
+    /// see [`ChatCompletion::extra`](crate::models::general::llm::ChatCompletion::extra).
+    pub extra: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// A floor on the length of a completion, see [`CallParams::min_length`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MinLength {
+    /// Fewest characters an acceptable reply has (roughly four per token).
+    pub chars: usize,
+    /// Most re-prompts sent while the reply stays shorter than `chars`.
+    pub retries: u32,
+}
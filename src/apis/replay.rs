@@ -0,0 +1,103 @@
+//! Recording of chat completion responses and replaying them without the network.
+//!
+//! A recording is a JSONL file with one `{"fingerprint": ..., "body": ...}` line per
+//! response, keyed by [`request_fingerprint`](crate::helpers::fingerprint::request_fingerprint).
+//! When a run sent the same request several times, replay serves the recorded responses
+//! in order and then keeps repeating the last one.
+use crate::apis::error::CallGptError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tokio::io::AsyncWriteExt;
+
+/// Whether a [`GptClient`](crate::apis::client::GptClient) records responses or replays
+/// them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayMode {
+    /// Send requests as usual and append every chat completion response to the file.
+    Record(PathBuf),
+    /// Serve chat completions from the file without contacting the API. A request with
+    /// no recorded response fails with [`CallGptError::ReplayMiss`], and streamed
+    /// completions and the other endpoints fail with
+    /// [`CallGptError::ReplayUnsupported`].
+    Replay(PathBuf),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Recorded {
+    fingerprint: String,
+    body: String,
+}
+
+// Shared by clones of one client so replay order is tracked across them
+#[derive(Debug)]
+pub(crate) struct Recorder {
+    mode: ReplayMode,
+    // How many responses have been served per fingerprint
+    served: Mutex<HashMap<String, usize>>,
+}
+
+impl Recorder {
+    pub(crate) fn new(mode: ReplayMode) -> Self {
+        Recorder {
+            mode,
+            served: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn is_replay(&self) -> bool {
+        matches!(self.mode, ReplayMode::Replay(_))
+    }
+
+    /// Returns the next recorded body for `fingerprint`.
+    pub(crate) async fn replay(&self, fingerprint: &str) -> Result<String, CallGptError> {
+        let ReplayMode::Replay(path) = &self.mode else {
+            unreachable!("replay called on a recording client");
+        };
+
+        let contents: String = tokio::fs::read_to_string(path).await?;
+        let mut bodies: Vec<String> = Vec::new();
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            let recorded: Recorded =
+                serde_json::from_str(line).map_err(|e| CallGptError::deserialize(e, line))?;
+            if recorded.fingerprint == fingerprint {
+                bodies.push(recorded.body);
+            }
+        }
+
+        let mut served = self.served.lock().unwrap_or_else(|e| e.into_inner());
+        let count: &mut usize = served.entry(fingerprint.to_string()).or_default();
+        let body: String = bodies
+            .get(*count)
+            .or(bodies.last())
+            .cloned()
+            .ok_or_else(|| CallGptError::ReplayMiss {
+                fingerprint: fingerprint.to_string(),
+            })?;
+        *count += 1;
+        Ok(body)
+    }
+
+    /// Appends `body` to the recording when in record mode.
+    pub(crate) async fn record(&self, fingerprint: &str, body: &str) -> Result<(), CallGptError> {
+        let ReplayMode::Record(path) = &self.mode else {
+            return Ok(());
+        };
+
+        let mut line: String = serde_json::to_string(&Recorded {
+            fingerprint: fingerprint.to_string(),
+            body: body.to_string(),
+        })
+        .expect("recorded responses always serialize");
+        line.push('\n');
+
+        let mut file: tokio::fs::File = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+//! Sharing of one in-flight request between concurrent identical calls.
+use crate::apis::error::CallGptError;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+/// Runs at most one operation per key at a time; callers that arrive while it is running
+/// wait for it and receive the same result instead of sending their own request.
+///
+/// This is not a cache: the key is forgotten as soon as the operation completes, so a
+/// later call runs again. A failure is shared too, so it costs one round trip however
+/// many callers were waiting: the caller that ran the operation gets its error and the
+/// others get a [`CallGptError::SharedFailure`] summarizing it.
+#[derive(Debug, Default)]
+pub(crate) struct SingleFlight<T> {
+    in_flight: Mutex<HashMap<String, Arc<Flight<T>>>>,
+}
+
+// The result, or the `SharedFailure` handed to waiters
+type Flight<T> = OnceCell<Result<T, CallGptError>>;
+
+impl<T: Clone> SingleFlight<T> {
+    pub(crate) async fn run<F, Fut>(&self, key: &str, op: F) -> Result<T, CallGptError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, CallGptError>>,
+    {
+        let cell: Arc<Flight<T>> = self.lock().entry(key.to_string()).or_default().clone();
+
+        let mut own_error: Option<CallGptError> = None;
+        let shared: &Result<T, CallGptError> = cell
+            .get_or_init(|| async {
+                op().await.map_err(|e| {
+                    let shared: CallGptError = e.shared();
+                    own_error = Some(e);
+                    shared
+                })
+            })
+            .await;
+        let result: Result<T, CallGptError> = match shared {
+            Ok(value) => Ok(value.clone()),
+            Err(shared) => Err(own_error.unwrap_or_else(|| shared.shared())),
+        };
+
+        // Unless a newer call has already replaced it, retire this flight
+        let mut in_flight = self.lock();
+        if in_flight
+            .get(key)
+            .is_some_and(|current| Arc::ptr_eq(current, &cell))
+        {
+            in_flight.remove(key);
+        }
+        result
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<Flight<T>>>> {
+        self.in_flight.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn tests_single_flight() {
+        let flights: SingleFlight<String> = SingleFlight::default();
+        let calls: AtomicU32 = AtomicU32::new(0);
+        let op = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok::<String, CallGptError>("shared".to_string())
+        };
+
+        let (first, second) = tokio::join!(flights.run("key", op), flights.run("key", op));
+        let later: String = flights.run("key", op).await.unwrap();
+
+        assert_eq!(first.unwrap(), "shared");
+        assert_eq!(second.unwrap(), "shared");
+        assert_eq!(later, "shared");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn tests_single_flight_shares_failure() {
+        let flights: SingleFlight<String> = SingleFlight::default();
+        let calls: AtomicU32 = AtomicU32::new(0);
+        let op = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Err::<String, CallGptError>(CallGptError::api(503, "overloaded"))
+        };
+
+        let (first, second) = tokio::join!(flights.run("key", op), flights.run("key", op));
+
+        assert!(matches!(first, Err(CallGptError::Api { status: 503, .. })));
+        let second: CallGptError = second.unwrap_err();
+        assert!(matches!(
+            second,
+            CallGptError::SharedFailure {
+                status: Some(503),
+                ..
+            }
+        ));
+        assert!(second.is_retryable());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,237 @@
+//! Decoding of the server-sent events returned by streaming chat completions.
+use crate::apis::error::CallGptError;
+use crate::models::general::llm::{APIStreamChunk, APIUsage};
+use tracing::warn;
+
+/// One event received from the stream.
+#[derive(Debug, PartialEq)]
+pub(crate) enum SseEvent {
+    /// The `data:` payload of an event, normally a JSON chunk.
+    Data(String),
+    /// The `[DONE]` sentinel that ends the stream.
+    Done,
+}
+
+/// Accumulates raw bytes and yields complete events, carrying any partial event over to
+/// the next read.
+///
+/// An event is only parsed once its terminating blank line (`\n\n`) has arrived, so
+/// reads that split a frame, a line or even a multi-byte character are handled.
+#[derive(Debug, Default)]
+pub(crate) struct SseDecoder {
+    buffer: Vec<u8>,
+}
+
+impl SseDecoder {
+    pub(crate) fn push(&mut self, bytes: &[u8]) -> Vec<SseEvent> {
+        // CR only ever appears in line endings, since JSON escapes it inside strings
+        self.buffer
+            .extend(bytes.iter().copied().filter(|byte| *byte != b'\r'));
+
+        let mut events: Vec<SseEvent> = Vec::new();
+        while let Some(end) = self.buffer.windows(2).position(|pair| pair == b"\n\n") {
+            let frame: Vec<u8> = self.buffer.drain(..end + 2).collect();
+            if let Some(event) = parse_frame(&String::from_utf8_lossy(&frame)) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    /// Number of bytes waiting for the rest of their event.
+    pub(crate) fn buffered_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Yields whatever is left in the buffer as a final event, for a body that ends
+    /// without a terminating blank line.
+    pub(crate) fn finish(&mut self) -> Vec<SseEvent> {
+        self.push(b"\n\n")
+    }
+}
+
+// Join the `data:` lines of one frame; comments, event names and IDs carry no content
+fn parse_frame(frame: &str) -> Option<SseEvent> {
+    let data: Vec<&str> = frame
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(|data| data.strip_prefix(' ').unwrap_or(data))
+        .collect();
+    if data.is_empty() {
+        return None;
+    }
+
+    let data: String = data.join("\n");
+    if data.trim() == "[DONE]" {
+        Some(SseEvent::Done)
+    } else {
+        Some(SseEvent::Data(data))
+    }
+}
+
+/// Turns decoded events into content deltas.
+///
+/// A frame whose JSON cannot be parsed is logged and skipped so one garbled frame does
+/// not abort an otherwise good stream. It is only reported as an error, by
+/// [`finish`](Self::finish), if the stream produced no content at all.
+#[derive(Debug, Default)]
+pub(crate) struct DeltaDecoder {
+    sse: SseDecoder,
+    done: bool,
+    yielded: bool,
+    skipped: Option<CallGptError>,
+    usage: Option<APIUsage>,
+}
+
+impl DeltaDecoder {
+    /// Decodes `bytes`, returning the content deltas of every frame they complete.
+    pub(crate) fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        let events: Vec<SseEvent> = self.sse.push(bytes);
+        self.deltas(events)
+    }
+
+    /// Number of bytes waiting for the rest of their event.
+    pub(crate) fn buffered_len(&self) -> usize {
+        self.sse.buffered_len()
+    }
+
+    /// Whether the `[DONE]` sentinel has been received.
+    pub(crate) fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Token usage reported by the stream, if a frame carried it.
+    pub(crate) fn usage(&self) -> Option<APIUsage> {
+        self.usage
+    }
+
+    /// Decodes any unterminated final frame once the body has ended. Returns the error
+    /// of a skipped frame if the stream produced no content.
+    pub(crate) fn finish(&mut self) -> Result<Vec<String>, CallGptError> {
+        let events: Vec<SseEvent> = self.sse.finish();
+        let deltas: Vec<String> = self.deltas(events);
+
+        match self.skipped.take() {
+            Some(e) if !self.yielded => Err(e),
+            _ => Ok(deltas),
+        }
+    }
+
+    fn deltas(&mut self, events: Vec<SseEvent>) -> Vec<String> {
+        let mut deltas: Vec<String> = Vec::new();
+        for event in events {
+            if self.done {
+                break;
+            }
+
+            match event {
+                SseEvent::Done => self.done = true,
+                SseEvent::Data(data) => match parse_chunk(&data) {
+                    Ok((content, usage)) => {
+                        self.usage = usage.or(self.usage);
+                        if let Some(content) = content {
+                            self.yielded = true;
+                            deltas.push(content);
+                        }
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "skipping malformed stream frame");
+                        self.skipped = Some(e);
+                    }
+                },
+            }
+        }
+        deltas
+    }
+}
+
+/// Extracts the content delta of the first choice and the usage, if present, from a JSON
+/// chunk.
+pub(crate) fn parse_chunk(data: &str) -> Result<(Option<String>, Option<APIUsage>), CallGptError> {
+    let chunk: APIStreamChunk =
+        serde_json::from_str(data).map_err(|e| CallGptError::deserialize(e, data))?;
+    let content: Option<String> = chunk
+        .choices
+        .into_iter()
+        .next()
+        .and_then(|choice| choice.delta.content);
+    Ok((content, chunk.usage))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &str = concat!(
+        "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\r\n\r\n",
+        ": keep-alive\n\n",
+        "data: {\"choices\":[{\"delta\":{\"content\":\"Grüß\"}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{\"cont\n\n",
+        "data: {\"choices\":[{\"delta\":{\"content\":\" dich\"}}]}\n\n",
+        "data: [DONE]\n\n",
+        "data: {\"choices\":[{\"delta\":{\"content\":\"ignored\"}}]}\n\n"
+    );
+
+    #[test]
+    fn tests_delta_decoder_split_frames() {
+        // Every split point, including inside the multi-byte `ü` and `ß`
+        for split in 0..=BODY.len() {
+            let (head, tail) = BODY.as_bytes().split_at(split);
+            let mut decoder: DeltaDecoder = DeltaDecoder::default();
+
+            let mut deltas: Vec<String> = decoder.push(head);
+            deltas.extend(decoder.push(tail));
+            assert!(decoder.is_done());
+            deltas.extend(decoder.finish().unwrap());
+
+            assert_eq!(deltas, vec!["Grüß".to_string(), " dich".to_string()]);
+        }
+    }
+
+    #[test]
+    fn tests_delta_decoder_byte_at_a_time() {
+        let mut decoder: DeltaDecoder = DeltaDecoder::default();
+
+        let mut deltas: Vec<String> = Vec::new();
+        for byte in BODY.as_bytes() {
+            deltas.extend(decoder.push(std::slice::from_ref(byte)));
+        }
+
+        assert_eq!(deltas.concat(), "Grüß dich");
+    }
+
+    #[test]
+    fn tests_delta_decoder_only_garbled_content() {
+        let mut decoder: DeltaDecoder = DeltaDecoder::default();
+
+        assert!(decoder
+            .push(b"data: {not json}\n\ndata: {\"choices\"")
+            .is_empty());
+
+        assert!(matches!(
+            decoder.finish(),
+            Err(CallGptError::Deserialize { .. })
+        ));
+    }
+
+    #[test]
+    fn tests_delta_decoder_usage() {
+        let mut decoder: DeltaDecoder = DeltaDecoder::default();
+
+        let deltas: Vec<String> = decoder.push(concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}],\"usage\":null}\n\n",
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":5,\"completion_tokens\":1,\"total_tokens\":6}}\n\n",
+            "data: [DONE]\n\n"
+        ).as_bytes());
+
+        assert_eq!(deltas, vec!["Hi".to_string()]);
+        assert_eq!(
+            decoder.usage(),
+            Some(APIUsage {
+                prompt_tokens: 5,
+                completion_tokens: 1,
+                total_tokens: 6,
+            })
+        );
+    }
+}
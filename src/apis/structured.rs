@@ -0,0 +1,176 @@
+//! Chat completions whose reply must be JSON of a known shape.
+use crate::apis::client::GptClient;
+use crate::apis::error::CallGptError;
+use crate::apis::params::CallParams;
+use crate::helpers::json_repair::extract_first_json;
+use crate::models::general::llm::Message;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tracing::warn;
+
+/// Number of replies `complete_json` requests before giving up on invalid JSON.
+pub const DEFAULT_JSON_ATTEMPTS: u32 = 3;
+
+const REPROMPT_INSTRUCTION: &str = "Your previous reply could not be used. \
+Reply again with only the corrected JSON.";
+
+/// Why a [`complete_validated`] validator rejected a reply.
+#[derive(Debug)]
+pub struct Rejection {
+    /// Sent back as a user message, after the rejected reply, to ask for a correction.
+    pub feedback: String,
+    /// Returned if the rejected reply was the last attempt.
+    pub error: CallGptError,
+}
+
+/// Sends `messages` and returns the first reply that `validate` accepts.
+///
+/// Each rejected reply is appended to the conversation, followed by its
+/// [`Rejection::feedback`] as a user message, and the model is asked again, up to
+/// `max_attempts` replies in total.
+///
+/// # Errors
+///
+/// Returns the [`Rejection::error`] of the last reply once every attempt has been
+/// rejected. Any error from the underlying calls is returned as-is.
+pub async fn complete_validated<T>(
+    client: &GptClient,
+    mut messages: Vec<Message>,
+    params: &CallParams,
+    max_attempts: u32,
+    validate: impl Fn(&str) -> Result<T, Rejection>,
+) -> Result<T, CallGptError> {
+    let mut attempt: u32 = 1;
+    loop {
+        let reply: String = client.complete_with(messages.clone(), params).await?;
+        let rejection: Rejection = match validate(&reply) {
+            Ok(value) => return Ok(value),
+            Err(rejection) => rejection,
+        };
+        if attempt >= max_attempts {
+            return Err(rejection.error);
+        }
+
+        warn!(attempt, error = %rejection.error, "reply was rejected, re-prompting");
+        messages.push(Message::new("assistant", reply));
+        messages.push(Message::new("user", rejection.feedback));
+        attempt += 1;
+    }
+}
+
+/// Sends `messages` and deserializes the first JSON value in the reply into `T`.
+///
+/// `T`'s `Deserialize` implementation acts as the schema: missing fields, wrong types
+/// and unknown variants are all mismatches. Prose or code fences around the JSON are
+/// tolerated (see [`extract_first_json`]). When the reply holds no JSON, or JSON of the
+/// wrong shape, the model is told what was wrong and asked again (see
+/// [`complete_validated`]).
+///
+/// # Errors
+///
+/// Once every attempt has failed, returns [`CallGptError::SchemaMismatch`] if the last
+/// reply held JSON of the wrong shape and [`CallGptError::Deserialize`] if it held no
+/// JSON at all. Any error from the underlying calls is returned as-is.
+pub async fn complete_json<T: DeserializeOwned>(
+    client: &GptClient,
+    messages: Vec<Message>,
+    params: &CallParams,
+    max_attempts: u32,
+) -> Result<T, CallGptError> {
+    complete_validated(client, messages, params, max_attempts, |reply| {
+        let err: serde_json::Error = match extract_first_json(reply) {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+        // Valid JSON that `T` rejects is a shape problem, not a syntax one
+        let rejection: Rejection = if extract_first_json::<Value>(reply).is_ok() {
+            Rejection {
+                feedback: format!(
+                    "{} The JSON did not match the expected schema: {}",
+                    REPROMPT_INSTRUCTION, err
+                ),
+                error: CallGptError::schema_mismatch(err.to_string(), reply),
+            }
+        } else {
+            Rejection {
+                feedback: format!("{} No valid JSON was found: {}", REPROMPT_INSTRUCTION, err),
+                error: CallGptError::deserialize(err, reply),
+            }
+        };
+        Err(rejection)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::test_support::{reply, test_client};
+    use crate::models::general::llm::SubTask;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer};
+
+    #[tokio::test]
+    async fn tests_complete_json_schema_mismatch() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(reply("{\"title\": 1, \"description\": \"Build it\"}"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains("did not match the expected schema"))
+            .respond_with(reply("{\"title\": 2, \"description\": \"Build it\"}"))
+            .expect(1)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+
+        let err: CallGptError =
+            complete_json::<SubTask>(&client, Vec::new(), &CallParams::default(), 2)
+                .await
+                .unwrap_err();
+
+        assert!(matches!(err, CallGptError::SchemaMismatch { .. }));
+        assert!(err
+            .to_string()
+            .starts_with("output didn't match expected schema: invalid type"));
+    }
+
+    #[tokio::test]
+    async fn tests_complete_validated_sends_feedback() {
+        let server: MockServer = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(reply("seven"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains("Reply with digits only."))
+            .respond_with(reply("7"))
+            .expect(1)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+
+        let client: GptClient = test_client(&server);
+
+        let number: u32 =
+            complete_validated(&client, Vec::new(), &CallParams::default(), 2, |reply| {
+                reply.trim().parse::<u32>().map_err(|e| Rejection {
+                    feedback: "Reply with digits only.".to_string(),
+                    error: CallGptError::schema_mismatch(e.to_string(), reply),
+                })
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(number, 7);
+    }
+}
@@ -0,0 +1,31 @@
+//! Fixtures shared by the unit tests of the `apis` modules.
+use crate::apis::client::{GptClient, GptConfig};
+use crate::models::general::llm::Message;
+use wiremock::{MockServer, ResponseTemplate};
+
+// Config pointing at the mock server, with the crate defaults otherwise
+pub(crate) fn test_config(server: &MockServer) -> GptConfig {
+    GptConfig {
+        base_url: server.uri(),
+        ..GptConfig::new("sk-config", "org-config")
+    }
+}
+
+pub(crate) fn test_client(server: &MockServer) -> GptClient {
+    GptClient::new(test_config(server)).unwrap()
+}
+
+// A chat completion whose only choice is an assistant reply of `content`
+pub(crate) fn reply(content: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(serde_json::json!({
+        "choices": [{ "message": { "role": "assistant", "content": content } }]
+    }))
+}
+
+pub(crate) fn message(role: &str, content: &str) -> Message {
+    Message::new(role, content)
+}
+
+pub(crate) fn user_messages() -> Vec<Message> {
+    vec![message("user", "Hello")]
+}
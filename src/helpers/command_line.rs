@@ -10,7 +10,7 @@ pub fn get_user_response(question: &str) -> String {
 
     // Print the question in a specific color
     stdout.execute(SetForegroundColor(Color::Blue)).unwrap();
-    println!("");
+    println!();
     println!("{}", question);
 
     // Reset color
@@ -23,5 +23,5 @@ pub fn get_user_response(question: &str) -> String {
         .expect("Failed to read response");
 
     // Trim whitespace and return
-    return user_response.trim().to_string();
+    user_response.trim().to_string()
 }
@@ -0,0 +1,63 @@
+//! Unified diffs between two versions of generated code.
+use crossterm::{
+    style::{Color, ResetColor, SetForegroundColor},
+    ExecutableCommand,
+};
+use similar::TextDiff;
+use std::io::stdout;
+
+// Unchanged lines shown around each change
+const CONTEXT_LINES: usize = 3;
+
+/// Returns a unified diff from `old` to `new`, with `old_label` and `new_label` as the
+/// `---` / `+++` file names. Identical inputs produce an empty string.
+pub fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(CONTEXT_LINES)
+        .header(old_label, new_label)
+        .to_string()
+}
+
+/// Prints a diff produced by [`unified_diff`] to the terminal, with removed lines in red,
+/// added lines in green and hunk headers in cyan.
+pub fn print_colored_diff(diff: &str) {
+    let mut stdout: std::io::Stdout = stdout();
+
+    for line in diff.lines() {
+        let color: Option<Color> = if line.starts_with("+++") || line.starts_with("---") {
+            None
+        } else if line.starts_with('+') {
+            Some(Color::Green)
+        } else if line.starts_with('-') {
+            Some(Color::Red)
+        } else if line.starts_with("@@") {
+            Some(Color::Cyan)
+        } else {
+            None
+        };
+
+        if let Some(color) = color {
+            stdout.execute(SetForegroundColor(color)).unwrap();
+        }
+        println!("{}", line);
+        stdout.execute(ResetColor).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_unified_diff() {
+        let old: &str = "fn main() {\n    println!(\"hi\");\n}\n";
+        let new: &str = "fn main() {\n    println!(\"hello\");\n}\n";
+
+        let diff: String = unified_diff(old, new, "v1/main.rs", "v2/main.rs");
+
+        assert!(diff.starts_with("--- v1/main.rs\n+++ v2/main.rs\n@@ -1,3 +1,3 @@\n"));
+        assert!(diff.contains("\n-    println!(\"hi\");\n+    println!(\"hello\");\n"));
+        assert!(unified_diff(old, old, "a", "b").is_empty());
+    }
+}
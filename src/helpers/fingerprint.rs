@@ -0,0 +1,76 @@
+//! Stable identifiers for chat completion requests.
+use crate::models::general::llm::ChatCompletion;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+/// Returns a stable SHA-256 hex digest identifying a chat completion request.
+///
+/// The request is serialized canonically (object keys sorted recursively, no
+/// whitespace) before hashing, so the key is identical across runs and does not
+/// depend on field or map ordering. Use it as a cache or deduplication key.
+pub fn request_fingerprint(req: &ChatCompletion) -> String {
+    let value: Value = serde_json::to_value(req).expect("ChatCompletion always serializes");
+    let canonical: String = canonicalize(value).to_string();
+
+    Sha256::digest(canonical.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// Rebuild every object with its keys in sorted order
+fn canonicalize(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+            let mut sorted: Map<String, Value> = Map::new();
+            for (key, value) in entries {
+                sorted.insert(key, canonicalize(value));
+            }
+            Value::Object(sorted)
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(canonicalize).collect()),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::general::llm::Message;
+
+    fn request(content: &str) -> ChatCompletion {
+        ChatCompletion {
+            model: "gpt-4o".to_string(),
+            messages: vec![Message::new("user", content)],
+            temperature: 0.1,
+            reasoning_effort: None,
+            max_tokens: None,
+            max_completion_tokens: None,
+            seed: None,
+            extra: None,
+        }
+    }
+
+    #[test]
+    fn tests_request_fingerprint() {
+        let fingerprint: String = request_fingerprint(&request("Hello"));
+
+        assert_eq!(fingerprint.len(), 64);
+        assert_eq!(fingerprint, request_fingerprint(&request("Hello")));
+        assert_ne!(fingerprint, request_fingerprint(&request("Goodbye")));
+    }
+
+    #[test]
+    fn tests_canonicalize_sorts_keys() {
+        let value: Value =
+            serde_json::json!({ "b": 1, "a": { "d": [{ "f": 1, "e": 2 }], "c": 2 } });
+
+        assert_eq!(
+            canonicalize(value).to_string(),
+            r#"{"a":{"c":2,"d":[{"e":2,"f":1}]},"b":1}"#
+        );
+    }
+}
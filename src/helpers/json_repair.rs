@@ -0,0 +1,199 @@
+//! Tolerant parsing of JSON produced by a model.
+use crate::helpers::markdown::extract_code_blocks;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// Parses JSON from model output that is only approximately JSON.
+///
+/// Strict parsing is tried first. Failing that, the text inside the first code fence (if
+/// any) is used, the first balanced `{...}` or `[...]` is extracted from any surrounding
+/// prose, and trailing commas before `}` or `]` are removed. Strings are respected
+/// throughout, so braces and commas inside them are left alone.
+///
+/// # Errors
+///
+/// Returns the strict parse error for the original text when no repair succeeds.
+pub fn parse_lenient_json(s: &str) -> Result<Value, serde_json::Error> {
+    let strict_err: serde_json::Error = match serde_json::from_str(s) {
+        Ok(value) => return Ok(value),
+        Err(e) => e,
+    };
+
+    let fenced: Option<String> = extract_code_blocks(s)
+        .into_iter()
+        .next()
+        .map(|(_, body)| body);
+    let text: &str = fenced.as_deref().unwrap_or(s);
+
+    let candidate: &str = first_balanced(text).unwrap_or(text);
+    serde_json::from_str(&remove_trailing_commas(candidate)).map_err(|_| strict_err)
+}
+
+/// Deserializes the first balanced JSON object or array embedded in a model response.
+///
+/// Unlike [`parse_lenient_json`] nothing is repaired: surrounding prose and code fences
+/// are skipped, and each balanced `{...}` or `[...]` is tried in order until one
+/// deserializes into `T`. Values nested inside a candidate are not tried on their own.
+///
+/// # Errors
+///
+/// Returns the error from the first candidate. A response without any candidate is
+/// parsed as a whole, so a bare scalar such as `42` still deserializes.
+pub fn extract_first_json<T: DeserializeOwned>(response: &str) -> Result<T, serde_json::Error> {
+    let mut first_err: Option<serde_json::Error> = None;
+    let mut rest: &str = response;
+
+    while let Some(start) = rest.find(['{', '[']) {
+        match first_balanced(&rest[start..]) {
+            Some(candidate) => {
+                match serde_json::from_str(candidate) {
+                    Ok(value) => return Ok(value),
+                    Err(e) => {
+                        first_err.get_or_insert(e);
+                    }
+                }
+                rest = &rest[start + candidate.len()..];
+            }
+            None => rest = &rest[start + 1..],
+        }
+    }
+
+    match first_err {
+        Some(e) => Err(e),
+        None => serde_json::from_str(response),
+    }
+}
+
+// The first `{...}` or `[...]` whose brackets balance, ignoring brackets inside strings
+fn first_balanced(text: &str) -> Option<&str> {
+    let start: usize = text.find(['{', '['])?;
+    let mut depth: usize = 0;
+    let mut in_string: bool = false;
+    let mut escaped: bool = false;
+
+    for (offset, c) in text[start..].char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&text[start..start + offset + 1]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// Drop commas that are followed, after optional whitespace, by a closing bracket
+fn remove_trailing_commas(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut repaired: String = String::with_capacity(text.len());
+    let mut in_string: bool = false;
+    let mut escaped: bool = false;
+
+    for (index, c) in chars.iter().copied().enumerate() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let next: Option<char> = chars[index + 1..]
+                .iter()
+                .copied()
+                .find(|next| !next.is_whitespace());
+            if matches!(next, Some('}') | Some(']')) {
+                continue;
+            }
+        }
+        repaired.push(c);
+    }
+    repaired
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn tests_parse_lenient_json() {
+        assert_eq!(
+            parse_lenient_json(r#"{"a": 1}"#).unwrap(),
+            json!({ "a": 1 })
+        );
+        assert_eq!(
+            parse_lenient_json("```json\n{\"files\": [\"main.rs\", \"lib.rs\",],}\n```").unwrap(),
+            json!({ "files": ["main.rs", "lib.rs"] })
+        );
+        assert_eq!(
+            parse_lenient_json(
+                "Sure! Here is the plan:\n[{\"step\": \"write {tests}, then code\"}]\nLet me know."
+            )
+            .unwrap(),
+            json!([{ "step": "write {tests}, then code" }])
+        );
+        assert_eq!(
+            parse_lenient_json(r#"{"quote": "a \"b,}\" c",}"#).unwrap(),
+            json!({ "quote": "a \"b,}\" c" })
+        );
+    }
+
+    #[test]
+    fn tests_parse_lenient_json_gives_up() {
+        assert!(parse_lenient_json("no JSON here").is_err());
+        assert!(parse_lenient_json("{\"unclosed\": [1, 2").is_err());
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Plan {
+        files: Vec<String>,
+    }
+
+    #[test]
+    fn tests_extract_first_json() {
+        let prose: Plan =
+            extract_first_json("Sure, here you go: {\"files\": [\"main.rs\"]} Anything else?")
+                .unwrap();
+        let fenced: Plan = extract_first_json(
+            "Here is the plan:\n```json\n{\"files\": [\"lib.rs\", \"main.rs\"]}\n```\n",
+        )
+        .unwrap();
+        let after_braces: Plan =
+            extract_first_json("Fill in {name} first, then: {\"files\": []}").unwrap();
+
+        assert_eq!(prose.files, vec!["main.rs".to_string()]);
+        assert_eq!(
+            fenced.files,
+            vec!["lib.rs".to_string(), "main.rs".to_string()]
+        );
+        assert!(after_braces.files.is_empty());
+    }
+
+    #[test]
+    fn tests_extract_first_json_rejects_mismatch() {
+        let wrong_shape: Result<Plan, serde_json::Error> =
+            extract_first_json("Result: {\"steps\": [\"a\"]}");
+        let no_json: Result<Plan, serde_json::Error> = extract_first_json("Sorry, I can't.");
+
+        assert!(wrong_shape.unwrap_err().to_string().contains("files"));
+        assert!(no_json.is_err());
+    }
+}
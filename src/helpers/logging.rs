@@ -0,0 +1,50 @@
+//! Initialization of the process-wide log subscriber.
+use std::env;
+use std::io;
+use tracing::Level;
+
+/// Output format of the log subscriber, chosen with the `LOG_FORMAT` environment variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// Human-readable, multi-line output for local use.
+    #[default]
+    Pretty,
+    /// One JSON object per event, including the fields of the enclosing spans, for log
+    /// pipelines.
+    Json,
+}
+
+impl LogFormat {
+    /// Reads `LOG_FORMAT` (`json` or `pretty`, case-insensitive). Unset or unrecognized
+    /// values fall back to [`LogFormat::Pretty`].
+    pub fn from_env() -> Self {
+        match env::var("LOG_FORMAT") {
+            Ok(value) if value.eq_ignore_ascii_case("json") => LogFormat::Json,
+            _ => LogFormat::Pretty,
+        }
+    }
+}
+
+/// Installs the global log subscriber in the format given by `LOG_FORMAT`, recording
+/// events at or above `LOG_LEVEL` (`error`, `warn`, `info`, `debug` or `trace`; `info`
+/// when unset or unrecognized). Use `debug` to see per-call details such as the system
+/// fingerprint of each response, and `trace` to also see every request exactly as it is
+/// sent, with credentials masked.
+///
+/// Logs go to stderr so they do not interleave with the interactive prompts on stdout.
+/// Call it once, at the start of `main`; later calls are ignored.
+pub fn init_logging() {
+    let level: Level = env::var("LOG_LEVEL")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(Level::INFO);
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(io::stderr);
+
+    // Fails only when a subscriber is already installed, which is fine to ignore
+    let _ = match LogFormat::from_env() {
+        LogFormat::Json => builder.json().try_init(),
+        LogFormat::Pretty => builder.pretty().try_init(),
+    };
+}
@@ -0,0 +1,158 @@
+//! Validation of generated `Cargo.toml` files before they are handed to cargo.
+use std::fmt;
+use toml::{Table, Value};
+
+const DEPENDENCY_TABLES: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+const DEPENDENCY_SOURCES: [&str; 4] = ["version", "path", "git", "workspace"];
+
+/// Error returned when a manifest would fail to build. The message is written to be fed
+/// back to the model as-is.
+#[derive(Debug)]
+pub enum ManifestError {
+    /// The manifest is not valid TOML.
+    Parse(toml::de::Error),
+    /// The manifest parsed but is missing required structure.
+    Invalid(String),
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestError::Parse(err) => write!(f, "Cargo.toml is not valid TOML: {}", err),
+            ManifestError::Invalid(reason) => write!(f, "invalid Cargo.toml: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ManifestError::Parse(err) => Some(err),
+            ManifestError::Invalid(_) => None,
+        }
+    }
+}
+
+/// Checks the basic structure of a `Cargo.toml`: it must parse, have a `[package]`
+/// section with a non-empty `name`, and every entry of its dependency tables must be a
+/// version requirement or a table naming a source (`version`, `path`, `git` or
+/// `workspace`).
+///
+/// This catches typos and malformed output early; it does not check that the named
+/// crates or versions exist. See
+/// [`complete_cargo_toml`](crate::apis::manifest::complete_cargo_toml) to re-prompt the
+/// model until its manifest passes.
+pub fn validate_cargo_toml(contents: &str) -> Result<(), ManifestError> {
+    let manifest: Table = contents.parse().map_err(ManifestError::Parse)?;
+
+    let package: &Table = manifest
+        .get("package")
+        .and_then(Value::as_table)
+        .ok_or_else(|| ManifestError::Invalid("missing `[package]` section".to_string()))?;
+    match package.get("name").and_then(Value::as_str) {
+        Some(name) if !name.trim().is_empty() => {}
+        _ => {
+            return Err(ManifestError::Invalid(
+                "`package.name` must be a non-empty string".to_string(),
+            ))
+        }
+    }
+
+    for section in DEPENDENCY_TABLES {
+        let Some(value) = manifest.get(section) else {
+            continue;
+        };
+        let dependencies: &Table = value
+            .as_table()
+            .ok_or_else(|| ManifestError::Invalid(format!("`[{}]` must be a table", section)))?;
+        for (name, spec) in dependencies {
+            validate_dependency(spec).map_err(|reason| {
+                ManifestError::Invalid(format!("{}.{} {}", section, name, reason))
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+// A dependency is `name = "1.0"` or `name = { version = "1.0", ... }`
+fn validate_dependency(spec: &Value) -> Result<(), String> {
+    match spec {
+        Value::String(requirement) => validate_requirement(requirement),
+        Value::Table(table) => {
+            if !DEPENDENCY_SOURCES
+                .iter()
+                .any(|key| table.contains_key(*key))
+            {
+                return Err(format!("must set one of {}", DEPENDENCY_SOURCES.join(", ")));
+            }
+            match table.get("version") {
+                None => Ok(()),
+                Some(Value::String(requirement)) => validate_requirement(requirement),
+                Some(_) => Err("has a non-string `version`".to_string()),
+            }
+        }
+        _ => Err("must be a version string or a table".to_string()),
+    }
+}
+
+fn validate_requirement(requirement: &str) -> Result<(), String> {
+    let valid: bool = requirement.chars().any(|c| c.is_ascii_digit() || c == '*')
+        && requirement
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || " .,*^~=<>+-".contains(c));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "has an invalid version requirement `{}`",
+            requirement
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_validate_cargo_toml() {
+        let manifest: &str = r#"
+[package]
+name = "demo"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+serde = { version = "1.0", features = ["derive"] }
+tokio = "^1.40"
+local = { path = "../local" }
+"#;
+
+        assert!(validate_cargo_toml(manifest).is_ok());
+    }
+
+    #[test]
+    fn tests_validate_cargo_toml_rejects_malformed() {
+        let unparsable: ManifestError =
+            validate_cargo_toml("[package\nname = \"demo\"").unwrap_err();
+        let no_package: ManifestError =
+            validate_cargo_toml("[dependencies]\nserde = \"1.0\"").unwrap_err();
+        let bad_version: ManifestError = validate_cargo_toml(
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = \"latest\"",
+        )
+        .unwrap_err();
+        let no_source: ManifestError = validate_cargo_toml(
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = { features = [\"derive\"] }",
+        )
+        .unwrap_err();
+
+        assert!(matches!(unparsable, ManifestError::Parse(_)));
+        assert!(no_package.to_string().contains("[package]"));
+        assert!(bad_version.to_string().contains("dependencies.serde"));
+        assert!(bad_version.to_string().contains("latest"));
+        assert!(no_source
+            .to_string()
+            .contains("version, path, git, workspace"));
+    }
+}
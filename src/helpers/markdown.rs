@@ -0,0 +1,107 @@
+//! Extraction of fenced code blocks from markdown replies.
+// A fence that opened a code block: its character and length
+struct Fence {
+    marker: char,
+    len: usize,
+}
+
+/// Extracts every fenced code block from a markdown document.
+///
+/// Returns one `(language, body)` pair per block, in order. The language is the first
+/// word of the opening fence's info string (`None` when absent) and the body excludes the
+/// fence lines. Fences may use backticks or tildes; a block only closes on a fence of the
+/// same character that is at least as long as the opening one, so a four-backtick block
+/// can contain triple-backtick text. An unclosed block runs to the end of the document.
+pub fn extract_code_blocks(markdown: &str) -> Vec<(Option<String>, String)> {
+    let mut blocks: Vec<(Option<String>, String)> = Vec::new();
+    let mut open: Option<(Fence, Option<String>, Vec<&str>)> = None;
+
+    for line in markdown.lines() {
+        match open.take() {
+            None => {
+                if let Some((fence, info)) = parse_fence(line) {
+                    let language: Option<String> =
+                        info.split_whitespace().next().map(str::to_string);
+                    open = Some((fence, language, Vec::new()));
+                }
+            }
+            Some((fence, language, mut body)) => match parse_fence(line) {
+                Some((closing, info))
+                    if closing.marker == fence.marker
+                        && closing.len >= fence.len
+                        && info.is_empty() =>
+                {
+                    blocks.push((language, body.join("\n")));
+                }
+                _ => {
+                    body.push(line);
+                    open = Some((fence, language, body));
+                }
+            },
+        }
+    }
+
+    if let Some((_, language, body)) = open {
+        blocks.push((language, body.join("\n")));
+    }
+    blocks
+}
+
+// Recognize a fence line (up to three spaces of indent, then three or more ` or ~)
+fn parse_fence(line: &str) -> Option<(Fence, &str)> {
+    let trimmed: &str = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+
+    let marker: char = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len: usize = trimmed.chars().take_while(|c| *c == marker).count();
+    if len < 3 {
+        return None;
+    }
+
+    let info: &str = trimmed[len..].trim();
+    // Backtick fences cannot have backticks in their info string
+    if marker == '`' && info.contains('`') {
+        return None;
+    }
+    Some((Fence { marker, len }, info))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_extract_code_blocks() {
+        let markdown: &str = "Here is the project:\n\n```toml\n[package]\nname = \"demo\"\n```\n\nAnd the code:\n\n```rust\nfn main() {\n    println!(\"hi\");\n}\n```\n";
+
+        let blocks: Vec<(Option<String>, String)> = extract_code_blocks(markdown);
+
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].0.as_deref(), Some("toml"));
+        assert_eq!(blocks[0].1, "[package]\nname = \"demo\"");
+        assert_eq!(blocks[1].0.as_deref(), Some("rust"));
+        assert_eq!(blocks[1].1, "fn main() {\n    println!(\"hi\");\n}");
+    }
+
+    #[test]
+    fn tests_extract_code_blocks_without_language() {
+        let blocks: Vec<(Option<String>, String)> =
+            extract_code_blocks("```\nplain text\n```\n~~~ sh\nls\n~~~");
+
+        assert_eq!(blocks[0], (None, "plain text".to_string()));
+        assert_eq!(blocks[1], (Some("sh".to_string()), "ls".to_string()));
+    }
+
+    #[test]
+    fn tests_extract_code_blocks_nested_backticks() {
+        let markdown: &str = "````markdown\nUse this:\n```rust\nlet x = 1;\n```\n````\n";
+
+        let blocks: Vec<(Option<String>, String)> = extract_code_blocks(markdown);
+
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].0.as_deref(), Some("markdown"));
+        assert_eq!(blocks[0].1, "Use this:\n```rust\nlet x = 1;\n```");
+    }
+}
@@ -1 +1,16 @@
 pub mod command_line;
+pub mod diff;
+pub mod fingerprint;
+pub mod json_repair;
+pub mod logging;
+pub mod manifest;
+pub mod markdown;
+pub mod model_name;
+pub mod prompt_file;
+pub mod redact;
+pub mod retry;
+pub mod safe_path;
+pub mod stream_file;
+pub mod template;
+pub mod tokens;
+pub mod transcript;
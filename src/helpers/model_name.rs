@@ -0,0 +1,63 @@
+//! Normalization of the model names users type into the IDs the API expects.
+
+// Aliases keyed by their compact form: lowercase with `-`, `_`, `.` and spaces removed
+const MODEL_ALIASES: [(&str, &str); 14] = [
+    ("gpt4o", "gpt-4o"),
+    ("4o", "gpt-4o"),
+    ("gpt4omini", "gpt-4o-mini"),
+    ("4omini", "gpt-4o-mini"),
+    ("gpt4", "gpt-4"),
+    ("gpt4turbo", "gpt-4-turbo"),
+    ("gpt41", "gpt-4.1"),
+    ("gpt41mini", "gpt-4.1-mini"),
+    ("gpt35", "gpt-3.5-turbo"),
+    ("gpt35turbo", "gpt-3.5-turbo"),
+    ("o1", "o1"),
+    ("o1mini", "o1-mini"),
+    ("o3", "o3"),
+    ("o3mini", "o3-mini"),
+];
+
+/// Maps common spellings of a model name (`gpt4o`, `gpt-4-o`, `4o`, `GPT-4o`, ...) to
+/// the canonical model ID. Names that are not a known alias, such as dated snapshots or
+/// fine-tuned models, are returned unchanged.
+pub fn normalize_model(name: &str) -> String {
+    let compact: String = name
+        .chars()
+        .filter(|c| !matches!(c, '-' | '_' | '.' | ' '))
+        .flat_map(char::to_lowercase)
+        .collect();
+
+    MODEL_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == compact)
+        .map(|(_, model)| model.to_string())
+        .unwrap_or_else(|| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_normalize_model() {
+        let cases: [(&str, &str); 12] = [
+            ("gpt-4o", "gpt-4o"),
+            ("gpt4o", "gpt-4o"),
+            ("gpt-4-o", "gpt-4o"),
+            ("4o", "gpt-4o"),
+            ("GPT-4o", "gpt-4o"),
+            ("gpt-4o mini", "gpt-4o-mini"),
+            ("gpt 4 turbo", "gpt-4-turbo"),
+            ("gpt-3.5", "gpt-3.5-turbo"),
+            ("gpt41", "gpt-4.1"),
+            ("o3mini", "o3-mini"),
+            ("gpt-4o-2024-08-06", "gpt-4o-2024-08-06"),
+            ("ft:gpt-4o-mini:acme::abc123", "ft:gpt-4o-mini:acme::abc123"),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(normalize_model(input), expected, "normalizing {}", input);
+        }
+    }
+}
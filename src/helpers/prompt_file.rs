@@ -0,0 +1,210 @@
+//! Loading of version-controlled prompt files.
+//!
+//! A prompt file is a JSON document holding the conversation to send and, optionally,
+//! the model and temperature to use:
+//!
+//! ```json
+//! {
+//!     "model": "gpt-4o",
+//!     "temperature": 0.2,
+//!     "messages": [
+//!         { "role": "system", "content": "You are a terse assistant." },
+//!         { "role": "user", "content": "Summarize the Rust ownership rules." }
+//!     ]
+//! }
+//! ```
+use crate::apis::call_request::{DEFAULT_MODEL, DEFAULT_TEMPERATURE};
+use crate::models::general::llm::{ChatCompletion, PromptFile};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const VALID_ROLES: [&str; 4] = ["system", "user", "assistant", "tool"];
+
+/// Error returned when a prompt file cannot be read or does not match the expected schema.
+#[derive(Debug)]
+pub enum PromptFileError {
+    /// The file could not be read.
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The file is not valid JSON or does not have the expected shape.
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    /// The file parsed but contains values the API would reject.
+    Invalid { path: PathBuf, reason: String },
+}
+
+impl fmt::Display for PromptFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PromptFileError::Io { path, source } => {
+                write!(
+                    f,
+                    "failed to read prompt file {}: {}",
+                    path.display(),
+                    source
+                )
+            }
+            PromptFileError::Parse { path, source } => {
+                write!(f, "malformed prompt file {}: {}", path.display(), source)
+            }
+            PromptFileError::Invalid { path, reason } => {
+                write!(f, "invalid prompt file {}: {}", path.display(), reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PromptFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PromptFileError::Io { source, .. } => Some(source),
+            PromptFileError::Parse { source, .. } => Some(source),
+            PromptFileError::Invalid { .. } => None,
+        }
+    }
+}
+
+impl PromptFile {
+    /// Builds a request from the prompt file, filling in the crate defaults for any
+    /// parameter the file leaves unset.
+    pub fn to_chat_completion(&self) -> ChatCompletion {
+        ChatCompletion {
+            model: self
+                .model
+                .clone()
+                .unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+            messages: self.messages.clone(),
+            temperature: self.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+            reasoning_effort: None,
+            max_tokens: None,
+            max_completion_tokens: None,
+            seed: None,
+            extra: None,
+        }
+    }
+}
+
+/// Reads and validates a prompt file.
+///
+/// Unknown top-level keys, missing message fields, an empty message list, unknown roles
+/// and temperatures outside `0.0..=2.0` are all rejected with a descriptive error.
+pub fn load_prompt_file(path: impl AsRef<Path>) -> Result<PromptFile, PromptFileError> {
+    let path: &Path = path.as_ref();
+
+    let contents: String = fs::read_to_string(path).map_err(|source| PromptFileError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let prompt: PromptFile =
+        serde_json::from_str(&contents).map_err(|source| PromptFileError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+    validate(&prompt).map_err(|reason| PromptFileError::Invalid {
+        path: path.to_path_buf(),
+        reason,
+    })?;
+
+    Ok(prompt)
+}
+
+// Check the values serde cannot check for us
+fn validate(prompt: &PromptFile) -> Result<(), String> {
+    if prompt.messages.is_empty() {
+        return Err("`messages` must contain at least one message".to_string());
+    }
+
+    for (index, message) in prompt.messages.iter().enumerate() {
+        if !VALID_ROLES.contains(&message.role.as_str()) {
+            return Err(format!(
+                "messages[{}] has unknown role `{}` (expected one of {})",
+                index,
+                message.role,
+                VALID_ROLES.join(", ")
+            ));
+        }
+        if message.role == "tool" && message.tool_call_id.is_none() {
+            return Err(format!(
+                "messages[{}] has role `tool` but no `tool_call_id`",
+                index
+            ));
+        }
+    }
+
+    if let Some(model) = &prompt.model {
+        if model.trim().is_empty() {
+            return Err("`model` must not be empty".to_string());
+        }
+    }
+
+    if let Some(temperature) = prompt.temperature {
+        if !(0.0..=2.0).contains(&temperature) {
+            return Err(format!(
+                "`temperature` must be between 0.0 and 2.0, got {}",
+                temperature
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_prompt(name: &str, contents: &str) -> PathBuf {
+        let path: PathBuf = std::env::temp_dir().join(format!(
+            "rust_autogpt_prompt_{}_{}.json",
+            name,
+            std::process::id()
+        ));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn tests_load_prompt_file() {
+        let path: PathBuf = write_prompt(
+            "valid",
+            r#"{ "temperature": 0.5, "messages": [{ "role": "user", "content": "Hi" }] }"#,
+        );
+
+        let prompt: PromptFile = load_prompt_file(&path).unwrap();
+        let chat_completion: ChatCompletion = prompt.to_chat_completion();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(chat_completion.model, DEFAULT_MODEL);
+        assert_eq!(chat_completion.temperature, 0.5);
+        assert_eq!(chat_completion.messages[0].content, "Hi");
+    }
+
+    #[test]
+    fn tests_load_prompt_file_rejects_malformed() {
+        let unknown_key: PathBuf = write_prompt(
+            "unknown_key",
+            r#"{ "temprature": 0.5, "messages": [{ "role": "user", "content": "Hi" }] }"#,
+        );
+        let bad_role: PathBuf = write_prompt(
+            "bad_role",
+            r#"{ "messages": [{ "role": "robot", "content": "Hi" }] }"#,
+        );
+
+        let unknown_key_err: PromptFileError = load_prompt_file(&unknown_key).unwrap_err();
+        let bad_role_err: PromptFileError = load_prompt_file(&bad_role).unwrap_err();
+        fs::remove_file(&unknown_key).unwrap();
+        fs::remove_file(&bad_role).unwrap();
+
+        assert!(matches!(unknown_key_err, PromptFileError::Parse { .. }));
+        assert!(unknown_key_err.to_string().contains("temprature"));
+        assert!(matches!(bad_role_err, PromptFileError::Invalid { .. }));
+        assert!(bad_role_err.to_string().contains("robot"));
+    }
+}
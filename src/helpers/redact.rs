@@ -0,0 +1,103 @@
+//! Masking of credentials in text that may end up in logs.
+
+const MASK: &str = "***";
+
+// Query and form parameters whose values are credentials (matched case-insensitively)
+const SECRET_PARAMS: [&str; 4] = ["api-key=", "api_key=", "apikey=", "access_token="];
+
+// Shortest run after `sk-` treated as a key, so prose like "sk-learn" is left alone
+const MIN_KEY_CHARS: usize = 16;
+
+/// Returns `s` with anything resembling a credential masked: OpenAI-style `sk-...` keys,
+/// `Bearer` tokens, and the values of `api-key`, `api_key`, `apikey` and `access_token`
+/// parameters (as used in Azure and other custom base URLs).
+///
+/// Masked values keep their prefix so the output still shows what kind of secret it was,
+/// e.g. `Bearer ***` or `sk-***`.
+pub fn redact(s: &str) -> String {
+    let lower: String = s.to_ascii_lowercase();
+    let mut redacted: String = String::with_capacity(s.len());
+    let mut i: usize = 0;
+
+    while i < s.len() {
+        let rest: &str = &lower[i..];
+        let at_word_start: bool = i == 0 || !is_token_byte(s.as_bytes()[i - 1]);
+
+        // Length of the prefix to keep and of the secret after it
+        let secret: Option<(usize, usize)> = if at_word_start
+            && rest.starts_with("sk-")
+            && token_len(&s[i + 3..]) >= MIN_KEY_CHARS
+        {
+            Some((3, token_len(&s[i + 3..])))
+        } else if at_word_start && rest.starts_with("bearer ") && value_len(&s[i + 7..]) > 0 {
+            Some((7, value_len(&s[i + 7..])))
+        } else {
+            SECRET_PARAMS
+                .iter()
+                .find(|param| at_word_start && rest.starts_with(*param))
+                .map(|param| (param.len(), value_len(&s[i + param.len()..])))
+                .filter(|(_, len)| *len > 0)
+        };
+
+        match secret {
+            Some((prefix_len, secret_len)) => {
+                redacted.push_str(&s[i..i + prefix_len]);
+                redacted.push_str(MASK);
+                i += prefix_len + secret_len;
+            }
+            None => {
+                let c: char = s[i..].chars().next().expect("i is on a char boundary");
+                redacted.push(c);
+                i += c.len_utf8();
+            }
+        }
+    }
+    redacted
+}
+
+fn is_token_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_'
+}
+
+// Length of the key-like run at the start of `s`
+fn token_len(s: &str) -> usize {
+    s.bytes().take_while(|byte| is_token_byte(*byte)).count()
+}
+
+// Length of a secret value: everything up to whitespace, a quote or a separator
+fn value_len(s: &str) -> usize {
+    s.bytes()
+        .take_while(|byte| !byte.is_ascii_whitespace() && !b"&\"',;)".contains(byte))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_redact() {
+        assert_eq!(
+            redact("Incorrect API key provided: sk-proj-AbCdEf0123456789xyz."),
+            "Incorrect API key provided: sk-***."
+        );
+        assert_eq!(
+            redact(r#"{"authorization": "Bearer sk-abc.def-123"}"#),
+            r#"{"authorization": "Bearer ***"}"#
+        );
+        assert_eq!(
+            redact("error sending request for url (https://x.openai.azure.com/chat?api-version=2024-02-01&api-key=0123abcd)"),
+            "error sending request for url (https://x.openai.azure.com/chat?api-version=2024-02-01&api-key=***)"
+        );
+        assert_eq!(
+            redact("install scikit-learn and sk-short; tasks-1234567890abcdefgh"),
+            "install scikit-learn and sk-short; tasks-1234567890abcdefgh"
+        );
+    }
+
+    #[test]
+    fn tests_redact_bearer_only_at_word_start() {
+        assert_eq!(redact("nobearer xyz"), "nobearer xyz");
+        assert_eq!(redact("auth=Bearer xyz"), "auth=Bearer ***");
+    }
+}
@@ -0,0 +1,156 @@
+//! Retrying of fallible async operations with exponential backoff.
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// How often and how patiently [`retry_with_backoff`] retries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first. `1` disables retrying.
+    pub max_attempts: u32,
+    /// Delay before the first retry. Each later retry waits twice as long as the one
+    /// before it.
+    pub base_delay: Duration,
+    /// Upper bound on any single delay.
+    pub max_delay: Duration,
+    /// Fraction (`0.0..=1.0`) of each delay that is randomized, so many clients that
+    /// failed together do not retry in lockstep. `0.0` gives fixed delays.
+    pub jitter: f64,
+}
+
+impl RetryPolicy {
+    /// A policy that makes a single attempt and never retries.
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    // Delay after the given failed attempt (1-based)
+    fn delay(&self, attempt: u32) -> Duration {
+        let exponential: Duration = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(self.max_delay);
+
+        let jitter: f64 = self.jitter.clamp(0.0, 1.0);
+        exponential.mul_f64(1.0 - jitter * random_fraction())
+    }
+}
+
+impl Default for RetryPolicy {
+    /// Three attempts, starting at 500ms and capped at 10s, with half of each delay
+    /// randomized.
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            jitter: 0.5,
+        }
+    }
+}
+
+/// Runs `op` until it succeeds, fails with an error `is_retryable` rejects, or
+/// `policy.max_attempts` attempts have been made, sleeping with exponential backoff
+/// between attempts.
+///
+/// # Errors
+///
+/// Returns the error of the last attempt.
+pub async fn retry_with_backoff<F, Fut, T, E>(
+    policy: &RetryPolicy,
+    mut op: F,
+    is_retryable: impl Fn(&E) -> bool,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt: u32 = 1;
+    loop {
+        match op().await {
+            Err(e) if attempt < policy.max_attempts && is_retryable(&e) => {
+                tokio::time::sleep(policy.delay(attempt)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+// A value in `0.0..1.0` from the randomly keyed std hasher, to avoid a `rand` dependency
+fn random_fraction() -> f64 {
+    let random: u64 = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn fast_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn tests_retry_with_backoff() {
+        let calls: Cell<u32> = Cell::new(0);
+
+        let result: Result<&str, &str> = retry_with_backoff(
+            &fast_policy(3),
+            || async {
+                calls.set(calls.get() + 1);
+                if calls.get() < 3 {
+                    Err("overloaded")
+                } else {
+                    Ok("done")
+                }
+            },
+            |_| true,
+        )
+        .await;
+
+        assert_eq!(result, Ok("done"));
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn tests_retry_with_backoff_stops() {
+        let calls: Cell<u32> = Cell::new(0);
+        let op = || async {
+            calls.set(calls.get() + 1);
+            Err::<(), &str>("bad request")
+        };
+
+        assert_eq!(
+            retry_with_backoff(&fast_policy(3), op, |e| *e != "bad request").await,
+            Err("bad request")
+        );
+        assert_eq!(calls.get(), 1);
+        assert_eq!(
+            retry_with_backoff(&fast_policy(2), op, |_| true).await,
+            Err("bad request")
+        );
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn tests_retry_policy_delay() {
+        let policy: RetryPolicy = RetryPolicy {
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+
+        assert_eq!(policy.delay(1), Duration::from_millis(500));
+        assert_eq!(policy.delay(3), Duration::from_secs(2));
+        assert_eq!(policy.delay(10), Duration::from_secs(10));
+    }
+}
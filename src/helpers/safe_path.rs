@@ -0,0 +1,107 @@
+//! Confinement of model-suggested file paths to an output directory.
+use std::fmt;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Error returned when a suggested path could escape the output directory.
+#[derive(Debug, PartialEq)]
+pub enum UnsafePathError {
+    /// The path is absolute, or has a root or drive prefix.
+    Absolute(PathBuf),
+    /// The path contains a `..` component.
+    Traversal(PathBuf),
+    /// The path names no file at all, e.g. `""` or `./`.
+    Empty,
+}
+
+impl fmt::Display for UnsafePathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnsafePathError::Absolute(path) => {
+                write!(f, "refusing absolute path {}", path.display())
+            }
+            UnsafePathError::Traversal(path) => {
+                write!(f, "refusing path with `..` traversal {}", path.display())
+            }
+            UnsafePathError::Empty => write!(f, "refusing empty path"),
+        }
+    }
+}
+
+impl std::error::Error for UnsafePathError {}
+
+impl From<UnsafePathError> for io::Error {
+    fn from(err: UnsafePathError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, err)
+    }
+}
+
+/// Resolves a path suggested by the model to a location inside `output_dir`.
+///
+/// Only plain relative paths are accepted: absolute paths, paths with a root or drive
+/// prefix and any `..` component are rejected, and `.` components are dropped. The
+/// check is purely lexical, so a symlink already inside `output_dir` can still point
+/// elsewhere.
+///
+/// # Errors
+///
+/// Returns an [`UnsafePathError`] describing why `suggested` was refused.
+pub fn confine_path(
+    output_dir: impl AsRef<Path>,
+    suggested: impl AsRef<Path>,
+) -> Result<PathBuf, UnsafePathError> {
+    let suggested: &Path = suggested.as_ref();
+    let mut relative: PathBuf = PathBuf::new();
+
+    for component in suggested.components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                return Err(UnsafePathError::Traversal(suggested.to_path_buf()))
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(UnsafePathError::Absolute(suggested.to_path_buf()))
+            }
+        }
+    }
+
+    if relative.as_os_str().is_empty() {
+        return Err(UnsafePathError::Empty);
+    }
+    Ok(output_dir.as_ref().join(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_confine_path() {
+        assert_eq!(
+            confine_path("out", "src/main.rs").unwrap(),
+            PathBuf::from("out/src/main.rs")
+        );
+        assert_eq!(
+            confine_path("out", "./src/./lib.rs").unwrap(),
+            PathBuf::from("out/src/lib.rs")
+        );
+    }
+
+    #[test]
+    fn tests_confine_path_rejects_escapes() {
+        assert!(matches!(
+            confine_path("out", "../../etc/passwd"),
+            Err(UnsafePathError::Traversal(_))
+        ));
+        assert!(matches!(
+            confine_path("out", "src/../../secrets"),
+            Err(UnsafePathError::Traversal(_))
+        ));
+        assert!(matches!(
+            confine_path("out", "/etc/passwd"),
+            Err(UnsafePathError::Absolute(_))
+        ));
+        assert_eq!(confine_path("out", "./"), Err(UnsafePathError::Empty));
+    }
+}
@@ -0,0 +1,144 @@
+//! Writing a streamed completion straight to disk.
+use crate::apis::error::CallGptError;
+use crate::helpers::safe_path::confine_path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::fs::File;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+// How often buffered output is pushed to the file while the stream is running
+const FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Appends every chunk of a streamed completion to `path` inside `output_dir`,
+/// truncating the file first. `path` is usually suggested by the model, so it is
+/// resolved with [`confine_path`] and may not escape `output_dir`. Missing parent
+/// directories are created.
+///
+/// Output is flushed at least every half second so the file can be watched while the
+/// model is still generating, and once more when the stream ends. Returns the number of
+/// bytes written.
+///
+/// # Errors
+///
+/// Returns [`CallGptError::Io`] if `path` is absolute or contains `..`, or if the file
+/// cannot be created or written, or the error the stream ended with. In the latter case
+/// everything received up to that point is left in the file.
+pub async fn write_stream_to_file(
+    mut chunks: mpsc::Receiver<Result<String, CallGptError>>,
+    output_dir: impl AsRef<Path>,
+    path: impl AsRef<Path>,
+) -> Result<u64, CallGptError> {
+    let path: PathBuf = confine_path(output_dir, path).map_err(std::io::Error::from)?;
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut writer: BufWriter<File> = BufWriter::new(File::create(path).await?);
+    let mut written: u64 = 0;
+    let mut last_flush: Instant = Instant::now();
+
+    while let Some(chunk) = chunks.recv().await {
+        let chunk: String = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                writer.flush().await?;
+                return Err(e);
+            }
+        };
+
+        writer.write_all(chunk.as_bytes()).await?;
+        written += chunk.len() as u64;
+
+        if last_flush.elapsed() >= FLUSH_INTERVAL {
+            writer.flush().await?;
+            last_flush = Instant::now();
+        }
+    }
+
+    writer.flush().await?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_name(name: &str) -> String {
+        format!("rust_autogpt_stream_{}_{}.rs", name, std::process::id())
+    }
+
+    #[tokio::test]
+    async fn tests_write_stream_to_file_truncates() {
+        let name: String = temp_name("truncate");
+        let path: PathBuf = std::env::temp_dir().join(&name);
+        std::fs::write(&path, "stale contents that are longer").unwrap();
+
+        let (tx, rx) = mpsc::channel(4);
+        tx.send(Ok("fn main() ".to_string())).await.unwrap();
+        tx.send(Ok("{}".to_string())).await.unwrap();
+        drop(tx);
+
+        let written: u64 = write_stream_to_file(rx, std::env::temp_dir(), &name)
+            .await
+            .unwrap();
+        let contents: String = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(written, 12);
+        assert_eq!(contents, "fn main() {}");
+    }
+
+    #[tokio::test]
+    async fn tests_write_stream_to_file_keeps_partial_output() {
+        let name: String = temp_name("partial");
+        let path: PathBuf = std::env::temp_dir().join(&name);
+
+        let (tx, rx) = mpsc::channel(4);
+        tx.send(Ok("fn main".to_string())).await.unwrap();
+        tx.send(Err(CallGptError::EmptyChoices)).await.unwrap();
+        drop(tx);
+
+        let err: CallGptError = write_stream_to_file(rx, std::env::temp_dir(), &name)
+            .await
+            .unwrap_err();
+        let contents: String = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(err, CallGptError::EmptyChoices));
+        assert_eq!(contents, "fn main");
+    }
+
+    #[tokio::test]
+    async fn tests_write_stream_to_file_rejects_escapes() {
+        let (tx, rx) = mpsc::channel(4);
+        tx.send(Ok("pwned".to_string())).await.unwrap();
+        drop(tx);
+
+        let err: CallGptError = write_stream_to_file(rx, std::env::temp_dir(), "../escape.rs")
+            .await
+            .unwrap_err();
+
+        assert!(
+            matches!(err, CallGptError::Io(ref e) if e.kind() == std::io::ErrorKind::InvalidInput)
+        );
+        assert!(err.to_string().contains("traversal"));
+    }
+
+    #[tokio::test]
+    async fn tests_write_stream_to_file_creates_parents() {
+        let output_dir: PathBuf = std::env::temp_dir().join(temp_name("nested"));
+
+        let (tx, rx) = mpsc::channel(4);
+        tx.send(Ok("fn main() {}".to_string())).await.unwrap();
+        drop(tx);
+
+        write_stream_to_file(rx, &output_dir, "src/main.rs")
+            .await
+            .unwrap();
+        let contents: String = std::fs::read_to_string(output_dir.join("src/main.rs")).unwrap();
+        std::fs::remove_dir_all(&output_dir).unwrap();
+
+        assert_eq!(contents, "fn main() {}");
+    }
+}
@@ -0,0 +1,117 @@
+//! Rendering of prompt templates with `{placeholder}` slots.
+use std::collections::HashMap;
+use std::fmt;
+
+/// Error returned when a template cannot be rendered.
+#[derive(Debug, PartialEq)]
+pub enum TemplateError {
+    /// The template names a placeholder that has no value.
+    UnresolvedKey(String),
+    /// A `{` was not closed by a matching `}`, or a lone `}` appeared.
+    Unbalanced { position: usize },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::UnresolvedKey(key) => {
+                write!(f, "no value for template placeholder `{{{}}}`", key)
+            }
+            TemplateError::Unbalanced { position } => {
+                write!(f, "unbalanced brace in template at byte {}", position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// Substitutes every `{key}` placeholder in `template` with its value from `vars`.
+///
+/// Use `{{` and `}}` for literal braces, e.g. when the prompt contains a JSON example.
+/// Unused entries in `vars` are ignored.
+///
+/// # Errors
+///
+/// Returns [`TemplateError::UnresolvedKey`] for a placeholder missing from `vars` and
+/// [`TemplateError::Unbalanced`] for a stray brace.
+pub fn render_template(
+    template: &str,
+    vars: &HashMap<&str, String>,
+) -> Result<String, TemplateError> {
+    let mut rendered: String = String::with_capacity(template.len());
+    let mut chars = template.char_indices().peekable();
+
+    while let Some((position, c)) = chars.next() {
+        match c {
+            '{' if chars.peek().map(|(_, next)| *next) == Some('{') => {
+                chars.next();
+                rendered.push('{');
+            }
+            '}' if chars.peek().map(|(_, next)| *next) == Some('}') => {
+                chars.next();
+                rendered.push('}');
+            }
+            '{' => {
+                let start: usize = position + 1;
+                let end: usize = loop {
+                    match chars.next() {
+                        Some((end, '}')) => break end,
+                        Some((_, '{')) | None => {
+                            return Err(TemplateError::Unbalanced { position })
+                        }
+                        Some(_) => {}
+                    }
+                };
+
+                let key: &str = template[start..end].trim();
+                let value: &String = vars
+                    .get(key)
+                    .ok_or_else(|| TemplateError::UnresolvedKey(key.to_string()))?;
+                rendered.push_str(value);
+            }
+            '}' => return Err(TemplateError::Unbalanced { position }),
+            _ => rendered.push(c),
+        }
+    }
+
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_render_template() {
+        let vars: HashMap<&str, String> = HashMap::from([
+            ("project", "a CLI todo app".to_string()),
+            ("language", "Rust".to_string()),
+        ]);
+
+        let rendered: String = render_template(
+            "Write {project} in {language}. Reply as {{\"code\": \"...\"}}.",
+            &vars,
+        )
+        .unwrap();
+
+        assert_eq!(
+            rendered,
+            "Write a CLI todo app in Rust. Reply as {\"code\": \"...\"}."
+        );
+    }
+
+    #[test]
+    fn tests_render_template_errors() {
+        let vars: HashMap<&str, String> = HashMap::from([("name", "x".to_string())]);
+
+        assert_eq!(
+            render_template("Hi {nmae}", &vars),
+            Err(TemplateError::UnresolvedKey("nmae".to_string()))
+        );
+        assert_eq!(
+            render_template("Hi {name", &vars),
+            Err(TemplateError::Unbalanced { position: 3 })
+        );
+    }
+}
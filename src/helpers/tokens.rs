@@ -0,0 +1,112 @@
+//! Rough token counting for sizing requests without a tokenizer.
+use std::fmt;
+use std::ops::Range;
+
+/// Average characters per token for English text and code under OpenAI's tokenizers.
+pub const CHARS_PER_TOKEN: usize = 4;
+
+/// Estimates how many tokens `text` will use, rounding up.
+///
+/// This is a heuristic: it is close for English prose and source code and undercounts
+/// for languages whose characters map to several tokens each.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Error returned when a chunk size cannot make progress through the text.
+#[derive(Debug, PartialEq)]
+pub struct ChunkSizeError {
+    pub chunk_tokens: usize,
+    pub overlap_tokens: usize,
+}
+
+impl fmt::Display for ChunkSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "chunks of {} tokens overlapping by {} cannot be split; \
+             chunk_tokens must be at least 1 and larger than the overlap",
+            self.chunk_tokens, self.overlap_tokens
+        )
+    }
+}
+
+impl std::error::Error for ChunkSizeError {}
+
+/// Splits `text` into byte ranges of at most `chunk_tokens` estimated tokens, each
+/// starting `overlap_tokens` before the end of the previous one.
+///
+/// Ranges always fall on `char` boundaries. Empty text yields no ranges.
+///
+/// # Errors
+///
+/// Returns a [`ChunkSizeError`] if `chunk_tokens` is zero or `overlap_tokens` is not
+/// smaller than it.
+pub fn split_into_token_chunks(
+    text: &str,
+    chunk_tokens: usize,
+    overlap_tokens: usize,
+) -> Result<Vec<Range<usize>>, ChunkSizeError> {
+    if overlap_tokens >= chunk_tokens {
+        return Err(ChunkSizeError {
+            chunk_tokens,
+            overlap_tokens,
+        });
+    }
+
+    // Byte offset of every char, plus the end of the text
+    let boundaries: Vec<usize> = text
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain(std::iter::once(text.len()))
+        .collect();
+    let char_count: usize = boundaries.len() - 1;
+
+    let chunk_chars: usize = chunk_tokens * CHARS_PER_TOKEN;
+    let step: usize = (chunk_tokens - overlap_tokens) * CHARS_PER_TOKEN;
+
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut start: usize = 0;
+    while start < char_count {
+        let end: usize = (start + chunk_chars).min(char_count);
+        ranges.push(boundaries[start]..boundaries[end]);
+        if end == char_count {
+            break;
+        }
+        start += step;
+    }
+    Ok(ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_split_into_token_chunks() {
+        let text: &str = "abcdefghijklmnopqrst";
+
+        let ranges: Vec<Range<usize>> = split_into_token_chunks(text, 2, 1).unwrap();
+
+        assert_eq!(estimate_tokens(text), 5);
+        assert_eq!(ranges, vec![0..8, 4..12, 8..16, 12..20]);
+        assert!(split_into_token_chunks("", 2, 1).unwrap().is_empty());
+        // Multi-byte characters are never split
+        assert_eq!(
+            split_into_token_chunks("ééééé", 1, 0).unwrap(),
+            vec![0..8, 8..10]
+        );
+    }
+
+    #[test]
+    fn tests_split_into_token_chunks_rejects_bad_sizes() {
+        assert_eq!(
+            split_into_token_chunks("abc", 0, 0),
+            Err(ChunkSizeError {
+                chunk_tokens: 0,
+                overlap_tokens: 0
+            })
+        );
+        assert!(split_into_token_chunks("abc", 2, 2).is_err());
+    }
+}
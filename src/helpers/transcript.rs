@@ -0,0 +1,146 @@
+//! Writing a conversation out for review after a run.
+use crate::helpers::safe_path::confine_path;
+use crate::helpers::tokens::estimate_tokens;
+use crate::models::general::llm::Message;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize)]
+struct Transcript<'a> {
+    written_at_unix: u64,
+    turns: Vec<Turn<'a>>,
+}
+
+#[derive(Serialize)]
+struct Turn<'a> {
+    role: &'a str,
+    content: &'a str,
+    estimated_tokens: usize,
+}
+
+/// Writes every message of a conversation, in order and with its role, to `path`
+/// inside `output_dir`. `path` is resolved with [`confine_path`] and may not escape
+/// `output_dir`. Missing parent directories are created.
+///
+/// A `.md` or `.markdown` extension produces a markdown transcript with one section per
+/// turn; anything else produces JSON. Each turn records its estimated token count (see
+/// [`estimate_tokens`]) and the transcript records when it was written. Messages carry no
+/// timestamps of their own, so per-turn times are not available.
+///
+/// # Errors
+///
+/// Returns an [`io::ErrorKind::InvalidInput`] error if `path` is absolute or contains
+/// `..`, and any error from writing the file.
+pub fn write_transcript(
+    messages: &[Message],
+    output_dir: impl AsRef<Path>,
+    path: impl AsRef<Path>,
+) -> io::Result<()> {
+    let path: PathBuf = confine_path(output_dir, path)?;
+    let written_at_unix: u64 = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let turns: Vec<Turn> = messages
+        .iter()
+        .map(|message| Turn {
+            role: &message.role,
+            content: &message.content,
+            estimated_tokens: estimate_tokens(&message.content),
+        })
+        .collect();
+
+    let is_markdown: bool = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| matches!(extension, "md" | "markdown"));
+
+    let contents: String = if is_markdown {
+        let mut markdown: String = format!(
+            "# Transcript\n\nWritten at {} (Unix time).\n",
+            written_at_unix
+        );
+        for (index, turn) in turns.iter().enumerate() {
+            markdown.push_str(&format!(
+                "\n## {}. {} (~{} tokens)\n\n{}\n",
+                index + 1,
+                turn.role,
+                turn.estimated_tokens,
+                turn.content
+            ));
+        }
+        markdown
+    } else {
+        let transcript: Transcript = Transcript {
+            written_at_unix,
+            turns,
+        };
+        serde_json::to_string_pretty(&transcript).expect("transcript always serializes")
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn tests_write_transcript() {
+        let messages: Vec<Message> = vec![
+            Message::new("user", "Build a todo app"),
+            Message::new("assistant", "Sure"),
+        ];
+        let json_name: String = format!("rust_autogpt_transcript_{}.json", std::process::id());
+        let json_path: PathBuf = std::env::temp_dir().join(&json_name);
+        let markdown_path: PathBuf = json_path.with_extension("md");
+
+        write_transcript(&messages, std::env::temp_dir(), &json_name).unwrap();
+        write_transcript(
+            &messages,
+            std::env::temp_dir(),
+            markdown_path.file_name().unwrap(),
+        )
+        .unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&json_path).unwrap()).unwrap();
+        let markdown: String = fs::read_to_string(&markdown_path).unwrap();
+        fs::remove_file(&json_path).unwrap();
+        fs::remove_file(&markdown_path).unwrap();
+
+        assert_eq!(json["turns"][0]["role"], "user");
+        assert_eq!(json["turns"][0]["estimated_tokens"], 4);
+        assert_eq!(json["turns"][1]["content"], "Sure");
+        assert!(markdown.contains("## 2. assistant (~1 tokens)\n\nSure\n"));
+    }
+
+    #[test]
+    fn tests_write_transcript_rejects_escapes() {
+        let err: io::Error =
+            write_transcript(&[], std::env::temp_dir(), "/etc/transcript.json").unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("absolute path"));
+    }
+
+    #[test]
+    fn tests_write_transcript_creates_parents() {
+        let output_dir: PathBuf = std::env::temp_dir().join(format!(
+            "rust_autogpt_transcript_nested_{}",
+            std::process::id()
+        ));
+
+        write_transcript(&[], &output_dir, "runs/first/transcript.md").unwrap();
+        let exists: bool = output_dir.join("runs/first/transcript.md").is_file();
+        fs::remove_dir_all(&output_dir).unwrap();
+
+        assert!(exists);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod ai_functions;
+pub mod apis;
+pub mod helpers;
+pub mod models;
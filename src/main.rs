@@ -1,11 +1,9 @@
-mod ai_functions;
-mod apis;
-mod helpers;
-mod models;
-
-use helpers::command_line::get_user_response;
+use rust_autogpt::helpers::command_line::get_user_response;
+use rust_autogpt::helpers::logging::init_logging;
 
 fn main() {
+    init_logging();
+
     let usr_req: String = get_user_response("What web server are we building today");
     dbg!(usr_req);
 }
@@ -1,9 +1,51 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::Range;
+use strum_macros::{Display, EnumString};
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Message {
     pub role: String,
     pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// ID of the tool call a `tool` message answers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    /// Tool calls requested by an `assistant` message, sent back unchanged when the
+    /// conversation continues with their results.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+}
+
+/// A function call requested by the model, answered by a `tool` message with a matching
+/// `tool_call_id`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: ToolCallFunction,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ToolCallFunction {
+    pub name: String,
+    /// The arguments as a JSON-encoded string, exactly as the model produced them.
+    pub arguments: String,
+}
+
+impl Message {
+    /// Creates a message with the given role and content and no optional fields.
+    pub fn new(role: impl Into<String>, content: impl Into<String>) -> Self {
+        Message {
+            role: role.into(),
+            content: content.into(),
+            name: None,
+            tool_call_id: None,
+            tool_calls: None,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -11,19 +53,281 @@ pub struct ChatCompletion {
     pub model: String,
     pub messages: Vec<Message>,
     pub temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<ReasoningEffort>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_completion_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Raw fields merged into the top level of the request body. This is an escape hatch
+    /// for parameters the crate does not model yet; keys that collide with the typed
+    /// fields above are sent twice, so avoiding collisions is the caller's responsibility.
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub extra: Option<serde_json::Map<String, serde_json::Value>>,
 }
 
-#[derive(Debug, Deserialize)]
+// `FromStr` accepts only "low", "medium" and "high"
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, EnumString, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
+// Response structs ignore unknown keys, so fields added to the API do not break deserialization
+#[derive(Debug, Deserialize, Clone)]
 pub struct APIMessage {
-    pub content: String,
+    #[serde(default)]
+    pub role: Option<String>,
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 pub struct APIChoice {
+    #[serde(default)]
+    pub index: u32,
     pub message: APIMessage,
+    #[serde(default)]
+    pub finish_reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct APIResponse {
     pub choices: Vec<APIChoice>,
+    #[serde(default)]
+    pub system_fingerprint: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StreamingChatCompletion<'a> {
+    #[serde(flatten)]
+    pub request: &'a ChatCompletion,
+    pub stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StreamOptions {
+    pub include_usage: bool,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct APIUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct APIStreamDelta {
+    #[serde(default)]
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct APIStreamChoice {
+    pub delta: APIStreamDelta,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct APIStreamChunk {
+    #[serde(default)]
+    pub choices: Vec<APIStreamChoice>,
+    // Only present on the final chunk, when usage was requested
+    #[serde(default)]
+    pub usage: Option<APIUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct APIModel {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct APIModelList {
+    pub data: Vec<APIModel>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompletionResult {
+    pub content: String,
+    pub model: String,
+    pub temperature: f32,
+    pub system_fingerprint: Option<String>,
+    /// Rate-limit headers of the response this result came from. `None` when it carried
+    /// none, or was replayed from a recording.
+    pub rate_limits: Option<RateLimits>,
+}
+
+/// The `x-ratelimit-*` headers OpenAI sends with each response. A field is `None` when
+/// its header is missing or malformed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RateLimits {
+    pub limit_requests: Option<u64>,
+    pub limit_tokens: Option<u64>,
+    pub remaining_requests: Option<u64>,
+    pub remaining_tokens: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StreamedCompletion {
+    pub content: String,
+    /// Token counts from the final stream frame. `None` unless
+    /// `CallParams::include_usage` was set and the server supports it.
+    pub usage: Option<APIUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct APIErrorDetail {
+    pub message: String,
+    #[serde(default)]
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct APIErrorResponse {
+    pub error: APIErrorDetail,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct EmbeddingRequest {
+    pub model: String,
+    pub input: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct APIEmbedding {
+    #[serde(default)]
+    pub index: usize,
+    pub embedding: Vec<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct APIEmbeddingResponse {
+    pub data: Vec<APIEmbedding>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EmbeddedChunk {
+    pub range: Range<usize>,
+    pub embedding: Vec<f32>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ModerationRequest {
+    pub input: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ModerationResult {
+    pub flagged: bool,
+    #[serde(default)]
+    pub categories: HashMap<String, bool>,
+    #[serde(default)]
+    pub category_scores: HashMap<String, f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModerationResponse {
+    pub results: Vec<ModerationResult>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SubTask {
+    pub title: String,
+    pub description: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PromptFile {
+    pub messages: Vec<Message>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_api_response_ignores_unknown_fields() {
+        let body: &str = r#"{
+            "id": "chatcmpl-123",
+            "object": "chat.completion",
+            "created": 1700000000,
+            "service_tier": "default",
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": "Hello!",
+                    "refusal": null,
+                    "annotations": []
+                },
+                "logprobs": null,
+                "finish_reason": "stop"
+            }],
+            "usage": { "prompt_tokens": 5, "completion_tokens": 2, "total_tokens": 7 }
+        }"#;
+
+        let res: APIResponse = serde_json::from_str(body).unwrap();
+
+        assert_eq!(res.choices[0].message.content.as_deref(), Some("Hello!"));
+    }
+
+    #[test]
+    fn tests_api_response_allows_null_content() {
+        let body: &str =
+            r#"{ "choices": [{ "message": { "role": "assistant", "content": null } }] }"#;
+
+        let res: APIResponse = serde_json::from_str(body).unwrap();
+
+        assert_eq!(res.choices[0].message.content, None);
+    }
+
+    #[test]
+    fn tests_message_name_is_optional() {
+        let plain: Message = Message::new("user", "Hi");
+        let named: Message = Message {
+            name: Some("alice".to_string()),
+            ..plain.clone()
+        };
+
+        assert_eq!(
+            serde_json::to_string(&plain).unwrap(),
+            r#"{"role":"user","content":"Hi"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&named).unwrap(),
+            r#"{"role":"user","content":"Hi","name":"alice"}"#
+        );
+        let parsed: Message = serde_json::from_str(r#"{"role":"user","content":"Hi"}"#).unwrap();
+        assert_eq!(parsed.name, None);
+    }
+
+    #[test]
+    fn tests_tool_message() {
+        let result: Message = Message {
+            tool_call_id: Some("call_abc123".to_string()),
+            ..Message::new("tool", r#"{"temperature": 21}"#)
+        };
+
+        let value: serde_json::Value = serde_json::to_value(&result).unwrap();
+        let parsed: Message = serde_json::from_value(value.clone()).unwrap();
+
+        assert_eq!(value["role"], "tool");
+        assert_eq!(value["tool_call_id"], "call_abc123");
+        assert_eq!(parsed.tool_call_id.as_deref(), Some("call_abc123"));
+    }
 }